@@ -0,0 +1,117 @@
+//! The built-in rules and search as a UCI engine on stdin/stdout, to play it against other
+//! engines in tournaments run by cutechess or any other UCI GUI.
+
+use std::{
+	io::{self, BufRead},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc
+	},
+	thread::{self, JoinHandle},
+	time::Duration
+};
+
+use chess_core::{search, Position, UciCommand, UciGo, UciInfo};
+
+// A search running in the background while commands keep coming in
+struct Search {
+	stop: Arc<AtomicBool>,
+	handle: JoinHandle<()>
+}
+
+impl Search {
+	fn start(position: Position, go: UciGo) -> Search {
+		let stop = Arc::new(AtomicBool::new(false));
+		let thread_stop = stop.clone();
+
+		let handle = thread::spawn(move || {
+			let limits = go.limits(position.side_to_move());
+			let result = search(&position, limits, &thread_stop, |info| println!("info {}", UciInfo::from(info)));
+
+			// An infinite search only gives its move once it's told to stop
+			while go.infinite && !thread_stop.load(Ordering::Relaxed) {
+				thread::sleep(Duration::from_millis(5));
+			}
+
+			match result.best_move {
+				Some(mv) => println!("bestmove {}", mv),
+				None => println!("bestmove 0000")
+			}
+		});
+
+		Search { stop, handle }
+	}
+
+	// Stop the search and wait for it to send its move
+	fn stop(self) {
+		self.stop.store(true, Ordering::Relaxed);
+		let _ = self.handle.join();
+	}
+}
+
+fn main() {
+	let mut position = Position::start();
+	let mut running: Option<Search> = None;
+
+	for line in io::stdin().lock().lines() {
+		let line = match line {
+			Ok(line) => line,
+			Err(_) => break
+		};
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let command = match UciCommand::parse(&line) {
+			Ok(command) => command,
+			Err(error) => {
+				println!("info string {}", error);
+				continue;
+			}
+		};
+
+		// Anything that changes the position or starts over ends the current search first
+		if !matches!(command, UciCommand::IsReady | UciCommand::Debug(_) | UciCommand::PonderHit) {
+			if let Some(search) = running.take() {
+				search.stop();
+			}
+		}
+
+		match command {
+			UciCommand::Uci => {
+				println!("id name Bevy Chess");
+				println!("id author Bevy Chess contributors");
+				println!("uciok");
+			},
+			UciCommand::IsReady => println!("readyok"),
+			UciCommand::UciNewGame => position = Position::start(),
+			UciCommand::Position { start, moves } => {
+				position = start;
+				for mv in moves {
+					if !position.is_legal(mv) {
+						println!("info string illegal move {}, ignoring the rest", mv);
+						break;
+					}
+					position.make_move(mv);
+				}
+			},
+			UciCommand::Go(go) => running = Some(Search::start(position.clone(), go)),
+			UciCommand::Perft(depth) => {
+				let divide = position.divide(depth);
+				for (mv, nodes) in &divide {
+					println!("{}: {}", mv, nodes);
+				}
+				println!();
+				// The position itself is the one node at depth 0
+				let nodes = if depth == 0 { 1 } else { divide.iter().map(|(_, nodes)| nodes).sum::<u64>() };
+				println!("Nodes searched: {}", nodes);
+			},
+			UciCommand::Quit => return,
+			UciCommand::Stop | UciCommand::Debug(_) | UciCommand::SetOption { .. } | UciCommand::PonderHit => {}
+		}
+	}
+
+	if let Some(search) = running {
+		search.stop();
+	}
+}
@@ -0,0 +1,273 @@
+//! The built-in rules and search as a CECP (XBoard/WinBoard) engine on stdin/stdout.
+
+use std::{
+	io::{self, BufRead},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Sender},
+		Arc
+	},
+	thread::{self, JoinHandle},
+	time::Duration
+};
+
+use chess_core::{parse_xboard_move, search, GameResult, Move, PgnResult, PieceColor, Position, SearchLimits, XBoardCommand, XBoardThinking};
+
+enum Input {
+	Line(String),
+	// A search finished, with the move it found
+	Searched(u32, Option<Move>)
+}
+
+struct Search {
+	// Tells the search apart from earlier ones whose moves may still be on the way
+	id: u32,
+	stop: Arc<AtomicBool>,
+	handle: JoinHandle<()>
+}
+
+impl Search {
+	fn start(id: u32, position: Position, limits: SearchLimits, post: bool, input: Sender<Input>) -> Search {
+		let stop = Arc::new(AtomicBool::new(false));
+		let thread_stop = stop.clone();
+
+		let handle = thread::spawn(move || {
+			let result = search(&position, limits, &thread_stop, |info| {
+				if post {
+					println!("{}", XBoardThinking::from_search(&position, info));
+				}
+			});
+
+			let _ = input.send(Input::Searched(id, result.best_move));
+		});
+
+		Search { id, stop, handle }
+	}
+
+	// Stop the search, its move is dropped
+	fn abort(self) {
+		self.stop.store(true, Ordering::Relaxed);
+		let _ = self.handle.join();
+	}
+}
+
+// Everything the engine keeps track of between commands
+struct Engine {
+	position: Position,
+	// Positions before each move, for undo
+	history: Vec<Position>,
+	// In force mode the engine only keeps track of the moves
+	force: bool,
+	color: PieceColor,
+	post: bool,
+	moves_per_control: u32,
+	increment: Duration,
+	fixed_time: Option<Duration>,
+	depth: Option<u32>,
+	clock: Option<Duration>,
+	running: Option<Search>,
+	searches: u32
+}
+
+impl Engine {
+	fn new() -> Engine {
+		Engine {
+			position: Position::start(),
+			history: Vec::new(),
+			force: false,
+			color: PieceColor::Black,
+			post: false,
+			moves_per_control: 0,
+			increment: Duration::ZERO,
+			fixed_time: None,
+			depth: None,
+			clock: None,
+			running: None,
+			searches: 0
+		}
+	}
+
+	fn abort_search(&mut self) {
+		if let Some(search) = self.running.take() {
+			search.abort();
+		}
+	}
+
+	fn limits(&self) -> SearchLimits {
+		let mut limits = match (self.fixed_time, self.clock) {
+			(Some(time), _) => SearchLimits::time(time),
+			(None, Some(clock)) => {
+				// Moves left until the next time control, if there are controls every few moves
+				let moves_to_go = match self.moves_per_control {
+					0 => None,
+					moves => Some(moves - (self.history.len() as u32 / 2) % moves)
+				};
+				SearchLimits::clock(clock, self.increment, moves_to_go)
+			},
+			(None, None) => SearchLimits::time(Duration::from_secs(5))
+		};
+		limits.depth = self.depth;
+
+		limits
+	}
+
+	// Think if it's the engine's turn and the game goes on
+	fn think(&mut self, input: &Sender<Input>) {
+		if self.force || self.running.is_some() || self.position.side_to_move() != self.color || self.result().is_some() {
+			return;
+		}
+
+		self.searches += 1;
+		self.running = Some(Search::start(self.searches, self.position.clone(), self.limits(), self.post, input.clone()));
+	}
+
+	fn play(&mut self, mv: Move) {
+		self.history.push(self.position.clone());
+		self.position.make_move(mv);
+
+		if let Some(result) = self.result() {
+			println!("{} {{{}}}", PgnResult::from(result), result);
+		}
+	}
+
+	// How the game ended, claiming draws by repetition and the fifty-move rule as soon as they come up
+	fn result(&self) -> Option<GameResult> {
+		let earlier = self.history.iter().map(Position::zobrist_hash).collect::<Vec<u64>>();
+
+		self.position
+			.game_result_with_history(&earlier)
+			.or_else(|| self.position.claimable_draw(&earlier).map(GameResult::Draw))
+	}
+
+	fn take_back(&mut self, plies: usize) {
+		self.abort_search();
+		for _ in 0..plies {
+			if let Some(position) = self.history.pop() {
+				self.position = position;
+			}
+		}
+	}
+
+	// Returns false once it's time to quit
+	fn handle(&mut self, line: &str, input: &Sender<Input>) -> bool {
+		let command = match XBoardCommand::parse(line) {
+			Ok(command) => command,
+			Err(_) => {
+				println!("Error (unknown command): {}", line);
+				return true;
+			}
+		};
+
+		match command {
+			XBoardCommand::Protover(_) => {
+				println!("feature myname=\"Bevy Chess\" usermove=1 setboard=1 ping=1 playother=0 san=0 sigint=0 sigterm=0 colors=0 analyze=0 variants=\"normal\" done=1");
+			},
+			XBoardCommand::New => {
+				self.abort_search();
+				*self = Engine {
+					post: self.post,
+					searches: self.searches,
+					..Engine::new()
+				};
+			},
+			XBoardCommand::Force => {
+				self.abort_search();
+				self.force = true;
+			},
+			XBoardCommand::Go => {
+				self.force = false;
+				self.color = self.position.side_to_move();
+				self.think(input);
+			},
+			XBoardCommand::UserMove(text) => match parse_xboard_move(&self.position, &text) {
+				Some(mv) => {
+					self.abort_search();
+					self.play(mv);
+					self.think(input);
+				},
+				None => println!("Illegal move: {}", text)
+			},
+			XBoardCommand::SetBoard(position) => {
+				self.abort_search();
+				self.position = position;
+				self.history.clear();
+			},
+			XBoardCommand::Level { moves, base, increment } => {
+				self.moves_per_control = moves;
+				self.increment = increment;
+				self.fixed_time = None;
+				self.clock = Some(base);
+			},
+			XBoardCommand::St(time) => self.fixed_time = Some(time),
+			XBoardCommand::Sd(depth) => self.depth = Some(depth),
+			XBoardCommand::Time(time) => self.clock = Some(time),
+			XBoardCommand::Result { .. } => {
+				self.abort_search();
+				self.force = true;
+			},
+			XBoardCommand::Ping(number) => println!("pong {}", number),
+			XBoardCommand::Undo => self.take_back(1),
+			XBoardCommand::Remove => self.take_back(2),
+			XBoardCommand::MoveNow => {
+				if let Some(search) = &self.running {
+					search.stop.store(true, Ordering::Relaxed);
+				}
+			},
+			XBoardCommand::Post => self.post = true,
+			XBoardCommand::NoPost => self.post = false,
+			XBoardCommand::Quit => return false,
+			XBoardCommand::XBoard | XBoardCommand::Accepted(_) | XBoardCommand::Rejected(_) | XBoardCommand::Otim(_) | XBoardCommand::Ignored(_) => {}
+		}
+
+		true
+	}
+}
+
+fn main() {
+	let (input, inputs) = mpsc::channel();
+
+	// Commands are read on their own thread so they can arrive while searching
+	let line_input = input.clone();
+	thread::spawn(move || {
+		for line in io::stdin().lock().lines() {
+			match line {
+				Ok(line) => {
+					if line_input.send(Input::Line(line)).is_err() {
+						break;
+					}
+				},
+				Err(_) => break
+			}
+		}
+		let _ = line_input.send(Input::Line("quit".to_string()));
+	});
+
+	let mut engine = Engine::new();
+
+	for message in inputs.iter() {
+		match message {
+			Input::Line(line) => {
+				if line.trim().is_empty() {
+					continue;
+				}
+				if !engine.handle(&line, &input) {
+					break;
+				}
+			},
+			Input::Searched(id, best_move) => {
+				// Moves from searches that were aborted don't count
+				if engine.running.as_ref().map(|search| search.id) != Some(id) {
+					continue;
+				}
+				engine.running = None;
+
+				if let Some(mv) = best_move {
+					println!("move {}", mv);
+					engine.play(mv);
+				}
+			}
+		}
+	}
+
+	engine.abort_search();
+}
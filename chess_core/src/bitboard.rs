@@ -0,0 +1,348 @@
+use std::{
+	ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not},
+	sync::OnceLock
+};
+
+use crate::{PieceColor, Square};
+
+/// A set of squares, one bit per square in [`Square::index`] order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+	pub const EMPTY: Bitboard = Bitboard(0);
+
+	pub fn from_square(square: Square) -> Bitboard {
+		Bitboard(1 << square.index())
+	}
+
+	/// Every square of a rank, 0 being white's back rank.
+	pub fn rank(rank: u8) -> Bitboard {
+		Bitboard(0xff << (rank * 8))
+	}
+
+	pub fn file(file: u8) -> Bitboard {
+		Bitboard(0x0101_0101_0101_0101 << file)
+	}
+
+	pub fn contains(&self, square: Square) -> bool {
+		self.0 & (1 << square.index()) != 0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0 == 0
+	}
+
+	pub fn count(&self) -> u32 {
+		self.0.count_ones()
+	}
+
+	/// The square with the lowest index.
+	pub fn first(&self) -> Option<Square> {
+		if self.is_empty() {
+			None
+		} else {
+			Some(Square::from_index(self.0.trailing_zeros() as usize))
+		}
+	}
+}
+
+/// Goes through the squares from a1 to h8.
+impl Iterator for Bitboard {
+	type Item = Square;
+
+	fn next(&mut self) -> Option<Square> {
+		let square = self.first()?;
+		self.0 &= self.0 - 1;
+		Some(square)
+	}
+}
+
+impl BitAnd for Bitboard {
+	type Output = Bitboard;
+
+	fn bitand(self, other: Bitboard) -> Bitboard {
+		Bitboard(self.0 & other.0)
+	}
+}
+
+impl BitOr for Bitboard {
+	type Output = Bitboard;
+
+	fn bitor(self, other: Bitboard) -> Bitboard {
+		Bitboard(self.0 | other.0)
+	}
+}
+
+impl BitXor for Bitboard {
+	type Output = Bitboard;
+
+	fn bitxor(self, other: Bitboard) -> Bitboard {
+		Bitboard(self.0 ^ other.0)
+	}
+}
+
+impl Not for Bitboard {
+	type Output = Bitboard;
+
+	fn not(self) -> Bitboard {
+		Bitboard(!self.0)
+	}
+}
+
+impl BitAndAssign for Bitboard {
+	fn bitand_assign(&mut self, other: Bitboard) {
+		self.0 &= other.0;
+	}
+}
+
+impl BitOrAssign for Bitboard {
+	fn bitor_assign(&mut self, other: Bitboard) {
+		self.0 |= other.0;
+	}
+}
+
+impl BitXorAssign for Bitboard {
+	fn bitxor_assign(&mut self, other: Bitboard) {
+		self.0 ^= other.0;
+	}
+}
+
+// (rank, file) steps
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i8, i8); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// Sliding attacks are looked up with "fancy" magic bitboards: the blockers that matter for a
+// square are multiplied by a magic number so their top bits index a table of attack sets.
+// The numbers were found by trying sparse random ones until no two blocker sets that give
+// different attacks ended up on the same index
+#[rustfmt::skip]
+const ROOK_MAGICS: [u64; 64] = [
+	0x0a80004000801220, 0x8040004010002008, 0x2080200010008008, 0x1100100008210004,
+	0xc200209084020008, 0x2100010004000208, 0x0400081000822421, 0x0200010422048844,
+	0x0800800080400024, 0x0001402000401000, 0x3000801000802001, 0x4400800800100083,
+	0x0904802402480080, 0x4040800400020080, 0x0018808042000100, 0x4040800080004100,
+	0x0040048001458024, 0x00a0004000205000, 0x3100808010002000, 0x4825010010000820,
+	0x5004808008000401, 0x2024818004000a00, 0x0005808002000100, 0x2100060004806104,
+	0x0080400880008421, 0x4062220600410280, 0x010a004a00108022, 0x0000100080080080,
+	0x0021000500080010, 0x0044000202001008, 0x0000100400080102, 0xc020128200040545,
+	0x0080002000400040, 0x0000804000802004, 0x0000120022004080, 0x010a386103001001,
+	0x9010080080800400, 0x8440020080800400, 0x0004228824001001, 0x000000490a000084,
+	0x0080002000504000, 0x200020005000c000, 0x0012088020420010, 0x0010010080080800,
+	0x0085001008010004, 0x0002000204008080, 0x0040413002040008, 0x0000304081020004,
+	0x0080204000800080, 0x3008804000290100, 0x1010100080200080, 0x2008100208028080,
+	0x5000850800910100, 0x8402019004680200, 0x0120911028020400, 0x0000008044010200,
+	0x0020850200244012, 0x0020850200244012, 0x0000102001040841, 0x140900040a100021,
+	0x000200282410a102, 0x000200282410a102, 0x000200282410a102, 0x4048240043802106
+];
+
+#[rustfmt::skip]
+const BISHOP_MAGICS: [u64; 64] = [
+	0x40106000a1160020, 0x0020010250810120, 0x2010010220280081, 0x002806004050c040,
+	0x0002021018000000, 0x2001112010000400, 0x0881010120218080, 0x1030820110010500,
+	0x0000120222042400, 0x2000020404040044, 0x8000480094208000, 0x0003422a02000001,
+	0x000a220210100040, 0x8004820202226000, 0x0018234854100800, 0x0100004042101040,
+	0x0004001004082820, 0x0010000810010048, 0x1014004208081300, 0x2080818802044202,
+	0x0040880c00a00100, 0x0080400200522010, 0x0001000188180b04, 0x0080249202020204,
+	0x1004400004100410, 0x00013100a0022206, 0x2148500001040080, 0x4241080011004300,
+	0x4020848004002000, 0x10101380d1004100, 0x0008004422020284, 0x01010a1041008080,
+	0x0808080400082121, 0x0808080400082121, 0x0091128200100c00, 0x0202200802010104,
+	0x8c0a020200440085, 0x01a0008080b10040, 0x0889520080122800, 0x100902022202010a,
+	0x04081a0816002000, 0x0000681208005000, 0x8170840041008802, 0x0a00004200810805,
+	0x0830404408210100, 0x2602208106006102, 0x1048300680802628, 0x2602208106006102,
+	0x0602010120110040, 0x0941010801043000, 0x000040440a210428, 0x0008240020880021,
+	0x0400002012048200, 0x00ac102001210220, 0x0220021002009900, 0x84440c080a013080,
+	0x0001008044200440, 0x0004c04410841000, 0x2000500104011130, 0x1a0c010011c20229,
+	0x0044800112202200, 0x0434804908100424, 0x0300404822c08200, 0x48081010008a2a80
+];
+
+struct Magic {
+	mask: u64,
+	magic: u64,
+	shift: u32,
+	offset: usize
+}
+
+struct AttackTables {
+	knight: [Bitboard; 64],
+	king: [Bitboard; 64],
+	pawn: [[Bitboard; 64]; 2],
+	rook_magics: Vec<Magic>,
+	bishop_magics: Vec<Magic>,
+	sliding: Vec<Bitboard>
+}
+
+// Built on first use
+fn tables() -> &'static AttackTables {
+	static TABLES: OnceLock<AttackTables> = OnceLock::new();
+	TABLES.get_or_init(AttackTables::new)
+}
+
+impl AttackTables {
+	fn new() -> AttackTables {
+		let step_attacks = |offsets: &[(i8, i8)]| {
+			let mut attacks = [Bitboard::EMPTY; 64];
+			for square in Square::all() {
+				for (rank, file) in offsets {
+					if let Some(to) = square.offset(*rank, *file) {
+						attacks[square.index()] |= Bitboard::from_square(to);
+					}
+				}
+			}
+
+			attacks
+		};
+
+		let mut pawn = [[Bitboard::EMPTY; 64]; 2];
+		for color in [PieceColor::White, PieceColor::Black] {
+			pawn[color.index()] = step_attacks(&[(color.pawn_direction(), 1), (color.pawn_direction(), -1)]);
+		}
+
+		let mut sliding = Vec::new();
+		let rook_magics = magic_tables(&ROOK_DIRECTIONS, &ROOK_MAGICS, &mut sliding);
+		let bishop_magics = magic_tables(&BISHOP_DIRECTIONS, &BISHOP_MAGICS, &mut sliding);
+
+		AttackTables {
+			knight: step_attacks(&KNIGHT_OFFSETS),
+			king: step_attacks(&KING_OFFSETS),
+			pawn,
+			rook_magics,
+			bishop_magics,
+			sliding
+		}
+	}
+}
+
+// Squares a slider reaches from `square`, stopping at (and including) the first blocker in each direction
+fn ray_attacks(square: Square, directions: &[(i8, i8)], blockers: u64) -> u64 {
+	let mut attacks = 0;
+	for (rank, file) in directions {
+		let mut current = square.offset(*rank, *file);
+		while let Some(to) = current {
+			attacks |= 1 << to.index();
+			if blockers & (1 << to.index()) != 0 {
+				break;
+			}
+			current = to.offset(*rank, *file);
+		}
+	}
+
+	attacks
+}
+
+// Blockers on the edge of the board never change the attacks, so they're left out of the mask
+fn relevant_blockers(square: Square, directions: &[(i8, i8)]) -> u64 {
+	let mut mask = 0;
+	for (rank, file) in directions {
+		let mut current = square.offset(*rank, *file);
+		while let Some(to) = current {
+			current = to.offset(*rank, *file);
+			if current.is_some() {
+				mask |= 1 << to.index();
+			}
+		}
+	}
+
+	mask
+}
+
+// Fills in the attacks for every set of blockers on every square, adding them to `table`
+fn magic_tables(directions: &[(i8, i8)], magics: &[u64; 64], table: &mut Vec<Bitboard>) -> Vec<Magic> {
+	Square::all()
+		.map(|square| {
+			let mask = relevant_blockers(square, directions);
+			let magic = Magic {
+				mask,
+				magic: magics[square.index()],
+				shift: 64 - mask.count_ones(),
+				offset: table.len()
+			};
+			table.resize(table.len() + (1 << mask.count_ones()), Bitboard::EMPTY);
+
+			// Every subset of the mask
+			let mut blockers = 0u64;
+			loop {
+				let index = magic.offset + (blockers.wrapping_mul(magic.magic) >> magic.shift) as usize;
+				let attacks = Bitboard(ray_attacks(square, directions, blockers));
+				// A wrong magic would silently give wrong attacks, so this is checked in release builds too
+				assert!(table[index].is_empty() || table[index] == attacks, "bad magic for {}", square);
+				table[index] = attacks;
+
+				blockers = blockers.wrapping_sub(mask) & mask;
+				if blockers == 0 {
+					break;
+				}
+			}
+
+			magic
+		})
+		.collect()
+}
+
+fn magic_attacks(magic: &Magic, occupied: Bitboard) -> Bitboard {
+	let index = ((occupied.0 & magic.mask).wrapping_mul(magic.magic) >> magic.shift) as usize;
+	tables().sliding[magic.offset + index]
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+	tables().knight[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+	tables().king[square.index()]
+}
+
+/// Squares a pawn of `color` takes on from `square`.
+pub fn pawn_attacks(color: PieceColor, square: Square) -> Bitboard {
+	tables().pawn[color.index()][square.index()]
+}
+
+/// Squares a rook reaches from `square`, up to and including the first piece of `occupied` in each direction.
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+	magic_attacks(&tables().rook_magics[square.index()], occupied)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+	magic_attacks(&tables().bishop_magics[square.index()], occupied)
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+	rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Every set of blockers on every square, plus the edge squares the masks leave out
+	fn check_lookups(directions: &[(i8, i8)], lookup: fn(Square, Bitboard) -> Bitboard) {
+		for square in Square::all() {
+			let mask = relevant_blockers(square, directions);
+			let edges = 0xff818181818181ff & !(1 << square.index());
+
+			let mut blockers = 0u64;
+			loop {
+				for occupied in [blockers, blockers | edges] {
+					assert_eq!(lookup(square, Bitboard(occupied)), Bitboard(ray_attacks(square, directions, occupied)), "{} with {:#x}", square, occupied);
+				}
+
+				blockers = blockers.wrapping_sub(mask) & mask;
+				if blockers == 0 {
+					break;
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn looks_up_rook_attacks() {
+		check_lookups(&ROOK_DIRECTIONS, rook_attacks);
+	}
+
+	#[test]
+	fn looks_up_bishop_attacks() {
+		check_lookups(&BISHOP_DIRECTIONS, bishop_attacks);
+	}
+}
@@ -0,0 +1,289 @@
+use std::{fmt, time::Duration};
+
+use crate::{DrawReason, GameResult, PieceColor, Position, SearchLimits};
+
+/// Time added to a player's clock for each move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeBonus {
+	None,
+	/// Fischer increment, added after every move.
+	Increment(Duration),
+	/// Bronstein delay: the time spent on a move is given back, up to this much.
+	Bronstein(Duration),
+	/// Simple delay: the clock only starts running once this much of the move has gone by.
+	Delay(Duration)
+}
+
+impl TimeBonus {
+	/// Roughly how much time each move gets back, for planning how long to think.
+	pub fn per_move(&self) -> Duration {
+		match self {
+			TimeBonus::None => Duration::ZERO,
+			TimeBonus::Increment(time) | TimeBonus::Bronstein(time) | TimeBonus::Delay(time) => *time
+		}
+	}
+}
+
+/// `time` to make `moves` moves in, or the rest of the game when `moves` is `None`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeStage {
+	pub moves: Option<u32>,
+	pub time: Duration,
+	pub bonus: TimeBonus
+}
+
+/// The stages a game is played in, such as 40 moves in 90 minutes and then 30 minutes for
+/// the rest. The last stage repeats if it has a number of moves.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimeControl {
+	pub stages: Vec<TimeStage>
+}
+
+impl TimeControl {
+	/// Sudden death in `time`, with `bonus` for every move.
+	pub fn new(time: Duration, bonus: TimeBonus) -> TimeControl {
+		TimeControl {
+			stages: vec![TimeStage {
+				moves: None,
+				time,
+				bonus
+			}]
+		}
+	}
+
+	/// Reads the PGN `TimeControl` tag format, in seconds: `300+2` for five minutes with a two
+	/// second increment, or `40/5400+30:1800+30` for 40 moves in 90 minutes and then 30 minutes,
+	/// both with 30 seconds a move. Delays are written `d` (simple) or `b` (Bronstein) instead of
+	/// `+`, such as `300d5`.
+	pub fn parse(text: &str) -> Option<TimeControl> {
+		let seconds = |text: &str| text.parse::<f64>().ok().filter(|seconds| *seconds >= 0.).map(Duration::from_secs_f64);
+
+		let stages = text.trim().split(':').map(|stage| {
+			let (moves, rest) = match stage.split_once('/') {
+				Some((moves, rest)) => (Some(moves.parse::<u32>().ok().filter(|moves| *moves > 0)?), rest),
+				None => (None, stage)
+			};
+
+			let (time, bonus) = match rest.find(['+', 'd', 'b']) {
+				Some(i) => {
+					let bonus = seconds(&rest[i + 1..])?;
+					(&rest[..i], match &rest[i..i + 1] {
+						"+" => TimeBonus::Increment(bonus),
+						"d" => TimeBonus::Delay(bonus),
+						_ => TimeBonus::Bronstein(bonus)
+					})
+				},
+				None => (rest, TimeBonus::None)
+			};
+
+			Some(TimeStage {
+				moves,
+				time: seconds(time)?,
+				bonus
+			})
+		}).collect::<Option<Vec<TimeStage>>>()?;
+
+		// Only the last stage can go on for the rest of the game
+		if stages[..stages.len() - 1].iter().any(|stage| stage.moves.is_none()) {
+			return None;
+		}
+
+		Some(TimeControl {
+			stages
+		})
+	}
+}
+
+impl fmt::Display for TimeControl {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (i, stage) in self.stages.iter().enumerate() {
+			if i > 0 {
+				write!(f, ":")?;
+			}
+			if let Some(moves) = stage.moves {
+				write!(f, "{}/", moves)?;
+			}
+			write!(f, "{}", stage.time.as_secs_f64())?;
+
+			match stage.bonus {
+				TimeBonus::None => {},
+				TimeBonus::Increment(time) => write!(f, "+{}", time.as_secs_f64())?,
+				TimeBonus::Delay(time) => write!(f, "d{}", time.as_secs_f64())?,
+				TimeBonus::Bronstein(time) => write!(f, "b{}", time.as_secs_f64())?
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Both players' clocks. Time isn't measured here: the side to move's clock is read and
+/// pressed with how long it has spent on the current move so far.
+#[derive(Clone, Debug)]
+pub struct ChessClock {
+	control: TimeControl,
+	remaining: [Duration; 2],
+	// The stage each side is in, and the moves it has made in it
+	stages: [usize; 2],
+	stage_moves: [u32; 2]
+}
+
+impl ChessClock {
+	pub fn new(control: TimeControl) -> ChessClock {
+		let time = control.stages[0].time;
+
+		ChessClock {
+			control,
+			remaining: [time; 2],
+			stages: [0; 2],
+			stage_moves: [0; 2]
+		}
+	}
+
+	pub fn control(&self) -> &TimeControl {
+		&self.control
+	}
+
+	fn stage(&self, color: PieceColor) -> &TimeStage {
+		&self.control.stages[self.stages[color.index()]]
+	}
+
+	/// What `color`'s clock shows after spending `spent` on its move.
+	pub fn time_left(&self, color: PieceColor, spent: Duration) -> Duration {
+		let charged = match self.stage(color).bonus {
+			TimeBonus::Delay(delay) => spent.saturating_sub(delay),
+			_ => spent
+		};
+
+		self.remaining[color.index()].saturating_sub(charged)
+	}
+
+	/// Whether `color` has run out of time after spending `spent` on its move.
+	pub fn is_flagged(&self, color: PieceColor, spent: Duration) -> bool {
+		self.time_left(color, spent).is_zero()
+	}
+
+	/// Moves `color` has to make before the time for its next stage is added, if any.
+	pub fn moves_to_go(&self, color: PieceColor) -> Option<u32> {
+		self.stage(color).moves.map(|moves| moves - self.stage_moves[color.index()])
+	}
+
+	/// `color` made a move after spending `spent` on it.
+	pub fn press(&mut self, color: PieceColor, spent: Duration) {
+		let stage = *self.stage(color);
+		let index = color.index();
+
+		self.remaining[index] = self.time_left(color, spent) + match stage.bonus {
+			TimeBonus::Increment(increment) => increment,
+			TimeBonus::Bronstein(delay) => spent.min(delay),
+			TimeBonus::None | TimeBonus::Delay(_) => Duration::ZERO
+		};
+
+		// Reaching the end of a stage adds the time for the next one
+		self.stage_moves[index] += 1;
+		if Some(self.stage_moves[index]) == stage.moves {
+			self.stages[index] = (self.stages[index] + 1).min(self.control.stages.len() - 1);
+			self.stage_moves[index] = 0;
+			self.remaining[index] += self.stage(color).time;
+		}
+	}
+
+	/// How long `color` should think about its move, given what's left on its clock.
+	pub fn search_limits(&self, color: PieceColor, spent: Duration) -> SearchLimits {
+		SearchLimits::clock(self.time_left(color, spent), self.stage(color).bonus.per_move(), self.moves_to_go(color))
+	}
+}
+
+impl Position {
+	/// How the game ends when `color` runs out of time: a loss, unless the opponent couldn't
+	/// checkmate anyway.
+	pub fn timeout_result(&self, color: PieceColor) -> GameResult {
+		if self.has_mating_material(color.opposite()) {
+			GameResult::Timeout {
+				winner: color.opposite()
+			}
+		} else {
+			GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+	use std::time::Duration;
+
+	fn secs(seconds: u64) -> Duration {
+		Duration::from_secs(seconds)
+	}
+
+	#[test]
+	fn parses_time_controls() {
+		assert_eq!(TimeControl::parse("300+2"), Some(TimeControl::new(secs(300), TimeBonus::Increment(secs(2)))));
+		assert_eq!(TimeControl::parse("60"), Some(TimeControl::new(secs(60), TimeBonus::None)));
+		assert_eq!(TimeControl::parse("300d5"), Some(TimeControl::new(secs(300), TimeBonus::Delay(secs(5)))));
+		assert_eq!(TimeControl::parse("300b5"), Some(TimeControl::new(secs(300), TimeBonus::Bronstein(secs(5)))));
+
+		let classical = TimeControl::parse("40/5400+30:1800+30").unwrap();
+		assert_eq!(classical.stages, vec![
+			TimeStage { moves: Some(40), time: secs(5400), bonus: TimeBonus::Increment(secs(30)) },
+			TimeStage { moves: None, time: secs(1800), bonus: TimeBonus::Increment(secs(30)) }
+		]);
+		assert_eq!(TimeControl::parse(&classical.to_string()), Some(classical));
+
+		for text in ["", "abc", "300+", "0/60", "60:40/60", "-5"] {
+			assert_eq!(TimeControl::parse(text), None, "{}", text);
+		}
+	}
+
+	#[test]
+	fn applies_bonuses() {
+		let mut clock = ChessClock::new(TimeControl::new(secs(60), TimeBonus::Increment(secs(2))));
+		clock.press(PieceColor::White, secs(5));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(57));
+		assert_eq!(clock.time_left(PieceColor::Black, secs(10)), secs(50));
+
+		// Bronstein gives back what was spent, up to the delay
+		let mut clock = ChessClock::new(TimeControl::new(secs(60), TimeBonus::Bronstein(secs(3))));
+		clock.press(PieceColor::White, secs(2));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(60));
+		clock.press(PieceColor::White, secs(5));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(58));
+
+		// A simple delay doesn't run the clock down at all at first
+		let mut clock = ChessClock::new(TimeControl::new(secs(60), TimeBonus::Delay(secs(3))));
+		assert_eq!(clock.time_left(PieceColor::White, secs(2)), secs(60));
+		assert_eq!(clock.time_left(PieceColor::White, secs(5)), secs(58));
+		clock.press(PieceColor::White, secs(5));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(58));
+
+		assert!(!clock.is_flagged(PieceColor::Black, secs(62)));
+		assert!(clock.is_flagged(PieceColor::Black, secs(63)));
+	}
+
+	#[test]
+	fn moves_through_stages() {
+		let mut clock = ChessClock::new(TimeControl::parse("2/60:30").unwrap());
+		assert_eq!(clock.moves_to_go(PieceColor::White), Some(2));
+
+		clock.press(PieceColor::White, secs(10));
+		assert_eq!(clock.moves_to_go(PieceColor::White), Some(1));
+		clock.press(PieceColor::White, secs(10));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(70));
+		assert_eq!(clock.moves_to_go(PieceColor::White), None);
+		assert_eq!(clock.moves_to_go(PieceColor::Black), Some(2));
+
+		// A last stage with a number of moves starts over
+		let mut clock = ChessClock::new(TimeControl::parse("1/60").unwrap());
+		clock.press(PieceColor::White, secs(30));
+		clock.press(PieceColor::White, secs(30));
+		assert_eq!(clock.time_left(PieceColor::White, Duration::ZERO), secs(120));
+	}
+
+	#[test]
+	fn timeout_against_bare_king_is_a_draw() {
+		let position = Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+		assert_eq!(position.timeout_result(PieceColor::White), GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial));
+		assert_eq!(position.timeout_result(PieceColor::Black), GameResult::Timeout { winner: PieceColor::White });
+	}
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::{Bitboard, GameResult, PieceColor, PieceType, Position};
+
+/// How a game ended in a draw, other than by stalemate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+	/// Claimed once the same position came up for the third time.
+	ThreefoldRepetition,
+	/// The same position five times, which ends the game without a claim.
+	FivefoldRepetition,
+	/// Claimed after fifty moves by each side without a capture or pawn move.
+	FiftyMoves,
+	/// Seventy-five moves each without a capture or pawn move, which ends the game without a claim.
+	SeventyFiveMoves,
+	/// Neither side has the pieces left to checkmate.
+	InsufficientMaterial,
+	/// Both players agreed to a draw.
+	Agreement,
+	/// A player ran out of time, but the opponent couldn't have checkmated them.
+	TimeoutVsInsufficientMaterial
+}
+
+impl fmt::Display for DrawReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", match self {
+			DrawReason::ThreefoldRepetition => "threefold repetition",
+			DrawReason::FivefoldRepetition => "fivefold repetition",
+			DrawReason::FiftyMoves => "the fifty-move rule",
+			DrawReason::SeventyFiveMoves => "the seventy-five-move rule",
+			DrawReason::InsufficientMaterial => "insufficient material",
+			DrawReason::Agreement => "agreement",
+			DrawReason::TimeoutVsInsufficientMaterial => "timeout against insufficient material"
+		})
+	}
+}
+
+// The light squares, to tell bishops that never meet apart
+const LIGHT_SQUARES: Bitboard = Bitboard(0x55aa_55aa_55aa_55aa);
+
+impl Position {
+	/// Whether neither side can checkmate whatever the moves: kings only, a single minor
+	/// piece, or bishops that all stand on squares of the same color.
+	pub fn has_insufficient_material(&self) -> bool {
+		let heavy = self.piece_type(PieceType::Pawn) | self.piece_type(PieceType::Rook) | self.piece_type(PieceType::Queen);
+		if !heavy.is_empty() {
+			return false;
+		}
+
+		let knights = self.piece_type(PieceType::Knight);
+		let bishops = self.piece_type(PieceType::Bishop);
+		if (knights | bishops).count() <= 1 {
+			return true;
+		}
+
+		knights.is_empty() && ((bishops & LIGHT_SQUARES).is_empty() || (bishops & !LIGHT_SQUARES).is_empty())
+	}
+
+	/// Whether `color` could checkmate with some series of legal moves, however unlikely,
+	/// which decides whether running out of time against it loses. A lone knight or
+	/// bishops on one color only mate with the opponent's pieces blocking their king.
+	pub fn has_mating_material(&self, color: PieceColor) -> bool {
+		let ours = self.color(color);
+		let theirs = self.color(color.opposite());
+
+		let heavy = self.piece_type(PieceType::Pawn) | self.piece_type(PieceType::Rook) | self.piece_type(PieceType::Queen);
+		if !(ours & heavy).is_empty() {
+			return true;
+		}
+
+		let knights = ours & self.piece_type(PieceType::Knight);
+		let bishops = ours & self.piece_type(PieceType::Bishop);
+		let their_pieces = theirs & !self.piece_type(PieceType::King);
+
+		match (knights.count(), bishops.count()) {
+			(0, 0) => false,
+			(1, 0) => !their_pieces.is_empty(),
+			(0, _) => {
+				let light = !(bishops & LIGHT_SQUARES).is_empty();
+				let dark = !(bishops & !LIGHT_SQUARES).is_empty();
+				if light && dark {
+					return true;
+				}
+
+				// The squares the bishops can't reach need blocking, which bishops on the same
+				// color as ours can't do
+				let bishop_squares = if light { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+				!(their_pieces & !(self.piece_type(PieceType::Bishop) & bishop_squares)).is_empty()
+			},
+			_ => true
+		}
+	}
+
+	/// How many times this position has come up, counting this one. `earlier` holds the
+	/// [`Position::zobrist_hash`] of every position before it in the game, oldest first.
+	pub fn repetitions(&self, earlier: &[u64]) -> usize {
+		let hash = self.zobrist_hash();
+
+		// Positions before the last capture or pawn move can't come up again, and only
+		// every other one has the same side to move
+		let reversible = (self.halfmove_clock() as usize).min(earlier.len());
+		let matches = earlier[earlier.len() - reversible..]
+			.iter()
+			.rev()
+			.skip(1)
+			.step_by(2)
+			.filter(|earlier_hash| **earlier_hash == hash)
+			.count();
+
+		matches + 1
+	}
+
+	/// A draw the side to move may claim: threefold repetition or the fifty-move rule.
+	pub fn claimable_draw(&self, earlier: &[u64]) -> Option<DrawReason> {
+		if self.repetitions(earlier) >= 3 {
+			Some(DrawReason::ThreefoldRepetition)
+		} else if self.halfmove_clock() >= 100 {
+			Some(DrawReason::FiftyMoves)
+		} else {
+			None
+		}
+	}
+
+	/// Like [`Position::game_result`], with the draws that end the game without a claim
+	/// as well. `earlier` is the same as for [`Position::repetitions`].
+	pub fn game_result_with_history(&self, earlier: &[u64]) -> Option<GameResult> {
+		// Checkmate on the last move before the seventy-five-move rule still counts
+		if let Some(result) = self.game_result() {
+			return Some(result);
+		}
+
+		if self.has_insufficient_material() {
+			Some(GameResult::Draw(DrawReason::InsufficientMaterial))
+		} else if self.repetitions(earlier) >= 5 {
+			Some(GameResult::Draw(DrawReason::FivefoldRepetition))
+		} else if self.halfmove_clock() >= 150 {
+			Some(GameResult::Draw(DrawReason::SeventyFiveMoves))
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	#[test]
+	fn finds_insufficient_material() {
+		for (fen, insufficient) in [
+			("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", true),
+			("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", false),
+			("4kn2/8/8/8/8/8/8/4KN2 w - - 0 1", false),
+			("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", false)
+		] {
+			assert_eq!(Position::from_fen(fen).unwrap().has_insufficient_material(), insufficient, "{}", fen);
+		}
+	}
+
+	#[test]
+	fn finds_mating_material() {
+		for (fen, white_can_mate) in [
+			("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", false),
+			("4k3/8/8/8/8/8/4p3/4KN2 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/2B1K1B1 w - - 0 1", false),
+			("4k3/8/8/8/8/8/3b4/2B1K1B1 w - - 0 1", false),
+			("4k3/8/8/8/8/8/4b3/2B1K1B1 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1", true),
+			("4k3/8/8/8/8/8/8/4K2R w - - 0 1", true)
+		] {
+			assert_eq!(Position::from_fen(fen).unwrap().has_mating_material(PieceColor::White), white_can_mate, "{}", fen);
+		}
+	}
+
+	#[test]
+	fn counts_repetitions() {
+		let mut position = Position::start();
+		let mut earlier = Vec::new();
+
+		// Both knights out and back twice gets to the start position a third time
+		for _ in 0..2 {
+			assert_eq!(position.claimable_draw(&earlier), None);
+			for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+				earlier.push(position.zobrist_hash());
+				position.make_move(Move::from_uci(mv).unwrap());
+			}
+		}
+
+		assert_eq!(position.repetitions(&earlier), 3);
+		assert_eq!(position.claimable_draw(&earlier), Some(DrawReason::ThreefoldRepetition));
+		assert_eq!(position.game_result_with_history(&earlier), None);
+
+		for _ in 0..2 {
+			for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+				earlier.push(position.zobrist_hash());
+				position.make_move(Move::from_uci(mv).unwrap());
+			}
+		}
+		assert_eq!(position.game_result_with_history(&earlier), Some(GameResult::Draw(DrawReason::FivefoldRepetition)));
+
+		let position = Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 100 80").unwrap();
+		assert_eq!(position.claimable_draw(&[]), Some(DrawReason::FiftyMoves));
+		let position = Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 150 105").unwrap();
+		assert_eq!(position.game_result_with_history(&[]), Some(GameResult::Draw(DrawReason::SeventyFiveMoves)));
+	}
+}
@@ -0,0 +1,165 @@
+use crate::{PieceColor, PieceType, Position};
+
+/// Material value of a piece in centipawns. Kings aren't counted.
+pub fn piece_value(piece_type: PieceType) -> i32 {
+	match piece_type {
+		PieceType::Pawn => 100,
+		PieceType::Knight => 320,
+		PieceType::Bishop => 330,
+		PieceType::Rook => 500,
+		PieceType::Queen => 900,
+		PieceType::King => 0
+	}
+}
+
+// Piece-square tables from white's side, laid out as seen from white with rank 8 on top
+
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+	 0,  0,   0,   0,   0,   0,  0,  0,
+	50, 50,  50,  50,  50,  50, 50, 50,
+	10, 10,  20,  30,  30,  20, 10, 10,
+	 5,  5,  10,  25,  25,  10,  5,  5,
+	 0,  0,   0,  20,  20,   0,  0,  0,
+	 5, -5, -10,   0,   0, -10, -5,  5,
+	 5, 10,  10, -20, -20,  10, 10,  5,
+	 0,  0,   0,   0,   0,   0,  0,  0
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+	-50, -40, -30, -30, -30, -30, -40, -50,
+	-40, -20,   0,   0,   0,   0, -20, -40,
+	-30,   0,  10,  15,  15,  10,   0, -30,
+	-30,   5,  15,  20,  20,  15,   5, -30,
+	-30,   0,  15,  20,  20,  15,   0, -30,
+	-30,   5,  10,  15,  15,  10,   5, -30,
+	-40, -20,   0,   5,   5,   0, -20, -40,
+	-50, -40, -30, -30, -30, -30, -40, -50
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+	-20, -10, -10, -10, -10, -10, -10, -20,
+	-10,   0,   0,   0,   0,   0,   0, -10,
+	-10,   0,   5,  10,  10,   5,   0, -10,
+	-10,   5,   5,  10,  10,   5,   5, -10,
+	-10,   0,  10,  10,  10,  10,   0, -10,
+	-10,  10,  10,  10,  10,  10,  10, -10,
+	-10,   5,   0,   0,   0,   0,   5, -10,
+	-20, -10, -10, -10, -10, -10, -10, -20
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+	 0,  0,  0,  0,  0,  0,  0,  0,
+	 5, 10, 10, 10, 10, 10, 10,  5,
+	-5,  0,  0,  0,  0,  0,  0, -5,
+	-5,  0,  0,  0,  0,  0,  0, -5,
+	-5,  0,  0,  0,  0,  0,  0, -5,
+	-5,  0,  0,  0,  0,  0,  0, -5,
+	-5,  0,  0,  0,  0,  0,  0, -5,
+	 0,  0,  0,  5,  5,  0,  0,  0
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+	-20, -10, -10, -5, -5, -10, -10, -20,
+	-10,   0,   0,  0,  0,   0,   0, -10,
+	-10,   0,   5,  5,  5,   5,   0, -10,
+	 -5,   0,   5,  5,  5,   5,   0,  -5,
+	  0,   0,   5,  5,  5,   5,   0,  -5,
+	-10,   5,   5,  5,  5,   5,   0, -10,
+	-10,   0,   5,  0,  0,   0,   0, -10,
+	-20, -10, -10, -5, -5, -10, -10, -20
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-20, -30, -30, -40, -40, -30, -30, -20,
+	-10, -20, -20, -20, -20, -20, -20, -10,
+	 20,  20,   0,   0,   0,   0,  20,  20,
+	 20,  30,  10,   0,   0,  10,  30,  20
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+	-50, -40, -30, -20, -20, -30, -40, -50,
+	-30, -20, -10,   0,   0, -10, -20, -30,
+	-30, -10,  20,  30,  30,  20, -10, -30,
+	-30, -10,  30,  40,  40,  30, -10, -30,
+	-30, -10,  30,  40,  40,  30, -10, -30,
+	-30, -10,  20,  30,  30,  20, -10, -30,
+	-30, -30,   0,   0,   0,   0, -30, -30,
+	-50, -30, -30, -30, -30, -30, -30, -50
+];
+
+/// Static evaluation in centipawns from the side to move's point of view:
+/// material plus piece-square tables.
+pub fn evaluate(position: &Position) -> i32 {
+	// Once the queens are off, or little else is left, the king should come out
+	let non_pawn_material = position
+		.pieces()
+		.filter(|(_, piece)| piece.piece_type != PieceType::Pawn)
+		.map(|(_, piece)| piece_value(piece.piece_type))
+		.sum::<i32>();
+	let endgame = non_pawn_material <= 2 * (piece_value(PieceType::Rook) + piece_value(PieceType::Knight));
+
+	let mut score = 0;
+	for (square, piece) in position.pieces() {
+		// Tables are written with rank 8 first, black reads them mirrored
+		let index = match piece.color {
+			PieceColor::White => (7 - square.rank() as usize) * 8 + square.file() as usize,
+			PieceColor::Black => square.rank() as usize * 8 + square.file() as usize
+		};
+
+		let table = match piece.piece_type {
+			PieceType::Pawn => &PAWN_TABLE,
+			PieceType::Knight => &KNIGHT_TABLE,
+			PieceType::Bishop => &BISHOP_TABLE,
+			PieceType::Rook => &ROOK_TABLE,
+			PieceType::Queen => &QUEEN_TABLE,
+			PieceType::King if endgame => &KING_ENDGAME_TABLE,
+			PieceType::King => &KING_MIDDLEGAME_TABLE
+		};
+
+		let value = piece_value(piece.piece_type) + table[index];
+		score += if piece.color == PieceColor::White { value } else { -value };
+	}
+
+	match position.side_to_move() {
+		PieceColor::White => score,
+		PieceColor::Black => -score
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	#[test]
+	fn scores_for_the_side_to_move() {
+		assert_eq!(evaluate(&Position::start()), 0);
+
+		// A knight up is good for white and just as bad for black
+		let white = Position::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+		let black = Position::from_fen("4k3/8/8/8/8/8/8/4KN2 b - - 0 1").unwrap();
+		assert!(evaluate(&white) > 200);
+		assert_eq!(evaluate(&black), -evaluate(&white));
+
+		// Mirroring the board swaps the sides
+		let mirrored = Position::from_fen("4kn2/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+		assert_eq!(evaluate(&mirrored), evaluate(&white));
+	}
+
+	#[test]
+	fn prefers_centralized_pieces() {
+		let center = Position::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+		let corner = Position::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+		assert!(evaluate(&center) > evaluate(&corner));
+	}
+}
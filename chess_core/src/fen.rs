@@ -0,0 +1,230 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{CastlingRights, Piece, PieceColor, PieceType, Position, Square};
+
+/// Forsyth–Edwards Notation of the standard starting position.
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+	/// The piece placement, side to move, castling or en passant field is missing.
+	MissingField(&'static str),
+	InvalidPlacement(String),
+	InvalidSideToMove(String),
+	InvalidCastling(String),
+	InvalidEnPassant(String),
+	InvalidCounter(String),
+	/// Each side needs exactly one king.
+	InvalidKings
+}
+
+impl fmt::Display for FenError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			FenError::MissingField(field) => write!(f, "missing {} field", field),
+			FenError::InvalidPlacement(placement) => write!(f, "invalid piece placement '{}'", placement),
+			FenError::InvalidSideToMove(side) => write!(f, "invalid side to move '{}'", side),
+			FenError::InvalidCastling(castling) => write!(f, "invalid castling rights '{}'", castling),
+			FenError::InvalidEnPassant(square) => write!(f, "invalid en passant square '{}'", square),
+			FenError::InvalidCounter(counter) => write!(f, "invalid move counter '{}'", counter),
+			FenError::InvalidKings => write!(f, "each side needs exactly one king")
+		}
+	}
+}
+
+impl Error for FenError {}
+
+impl Position {
+	/// Reads a position from FEN. The halfmove clock and fullmove number may be left out.
+	pub fn from_fen(fen: &str) -> Result<Position, FenError> {
+		let mut fields = fen.split_whitespace();
+		let mut position = Position::empty();
+
+		// Piece placement, from rank 8 down to rank 1
+		let placement = fields.next().ok_or(FenError::MissingField("piece placement"))?;
+		let ranks = placement.split('/').collect::<Vec<&str>>();
+		if ranks.len() != 8 {
+			return Err(FenError::InvalidPlacement(placement.to_string()));
+		}
+
+		for (i, rank_text) in ranks.iter().enumerate() {
+			let rank = 7 - i as u8;
+			let mut file = 0;
+
+			for c in rank_text.chars() {
+				if let Some(empty) = c.to_digit(10) {
+					file += empty as u8;
+				} else if let Some(piece) = Piece::from_char(c) {
+					if file >= 8 {
+						return Err(FenError::InvalidPlacement(placement.to_string()));
+					}
+
+					position.set_piece(Square::new(rank, file), Some(piece));
+					file += 1;
+				} else {
+					return Err(FenError::InvalidPlacement(placement.to_string()));
+				}
+			}
+
+			if file != 8 {
+				return Err(FenError::InvalidPlacement(placement.to_string()));
+			}
+		}
+
+		for color in [PieceColor::White, PieceColor::Black] {
+			let kings = position
+				.pieces()
+				.filter(|(_, piece)| *piece == Piece::new(color, PieceType::King))
+				.count();
+			if kings != 1 {
+				return Err(FenError::InvalidKings);
+			}
+		}
+
+		let side_to_move = fields.next().ok_or(FenError::MissingField("side to move"))?;
+		position.set_side_to_move(match side_to_move {
+			"w" => PieceColor::White,
+			"b" => PieceColor::Black,
+			_ => return Err(FenError::InvalidSideToMove(side_to_move.to_string()))
+		});
+
+		let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+		let mut castling_rights = CastlingRights::none();
+		if castling != "-" {
+			for c in castling.chars() {
+				match c {
+					'K' => castling_rights.white_king_side = true,
+					'Q' => castling_rights.white_queen_side = true,
+					'k' => castling_rights.black_king_side = true,
+					'q' => castling_rights.black_queen_side = true,
+					_ => return Err(FenError::InvalidCastling(castling.to_string()))
+				}
+			}
+		}
+		position.set_castling_rights(castling_rights);
+
+		let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+		if en_passant != "-" {
+			// The square a pawn of the side that just moved skipped over, with that pawn right past it
+			let color = position.side_to_move();
+			let pawn = Piece::new(color.opposite(), PieceType::Pawn);
+			let square = Square::from_name(en_passant)
+				.filter(|square| square.rank() == match color {
+					PieceColor::White => 5,
+					PieceColor::Black => 2
+				})
+				.filter(|square| square.offset(-color.pawn_direction(), 0).and_then(|pawn_square| position.piece_at(pawn_square)) == Some(pawn))
+				.ok_or_else(|| FenError::InvalidEnPassant(en_passant.to_string()))?;
+			position.set_en_passant(Some(square));
+		}
+
+		if let Some(halfmove_clock) = fields.next() {
+			position.set_halfmove_clock(halfmove_clock.parse().map_err(|_| FenError::InvalidCounter(halfmove_clock.to_string()))?);
+		}
+
+		if let Some(fullmove_number) = fields.next() {
+			position.set_fullmove_number(fullmove_number.parse().map_err(|_| FenError::InvalidCounter(fullmove_number.to_string()))?);
+		}
+
+		Ok(position)
+	}
+
+	pub fn to_fen(&self) -> String {
+		let mut fen = String::new();
+
+		for rank in (0..8).rev() {
+			let mut empty = 0;
+
+			for file in 0..8 {
+				match self.piece_at(Square::new(rank, file)) {
+					Some(piece) => {
+						if empty > 0 {
+							fen.push_str(&empty.to_string());
+							empty = 0;
+						}
+						fen.push(piece.to_char());
+					},
+					None => empty += 1
+				}
+			}
+
+			if empty > 0 {
+				fen.push_str(&empty.to_string());
+			}
+			if rank > 0 {
+				fen.push('/');
+			}
+		}
+
+		fen.push_str(match self.side_to_move() {
+			PieceColor::White => " w ",
+			PieceColor::Black => " b "
+		});
+
+		let castling_rights = self.castling_rights();
+		let castling = [
+			(castling_rights.white_king_side, 'K'),
+			(castling_rights.white_queen_side, 'Q'),
+			(castling_rights.black_king_side, 'k'),
+			(castling_rights.black_queen_side, 'q')
+		]
+		.iter()
+		.filter(|(allowed, _)| *allowed)
+		.map(|(_, c)| *c)
+		.collect::<String>();
+
+		if castling.is_empty() {
+			fen.push('-');
+		} else {
+			fen.push_str(&castling);
+		}
+
+		match self.en_passant() {
+			Some(square) => fen.push_str(&format!(" {}", square)),
+			None => fen.push_str(" -")
+		}
+
+		fen.push_str(&format!(" {} {}", self.halfmove_clock(), self.fullmove_number()));
+
+		fen
+	}
+}
+
+impl FromStr for Position {
+	type Err = FenError;
+
+	fn from_str(fen: &str) -> Result<Position, FenError> {
+		Position::from_fen(fen)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	#[test]
+	fn reads_en_passant_squares() {
+		let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3";
+		let position = Position::from_fen(fen).unwrap();
+		assert_eq!(position.en_passant(), Square::from_name("e3"));
+		assert_eq!(position.to_fen(), fen);
+		assert!(position.legal_moves().contains(&Move::from_uci("d4e3").unwrap()));
+	}
+
+	#[test]
+	fn rejects_impossible_en_passant_squares() {
+		for fen in [
+			// On the side to move's own third rank
+			"4k3/8/8/8/8/8/3P4/4K3 w - e3 0 1",
+			"4k3/3p4/8/8/8/8/8/4K3 b - e6 0 1",
+			// Without the pawn that skipped over it
+			"4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+			"4k3/8/8/4P3/8/8/8/4K3 w - e6 0 1",
+			"4k3/8/8/8/8/8/8/4K3 b - d3 0 1",
+			// Off the third and sixth ranks
+			"4k3/8/8/8/4p3/8/8/4K3 w - e4 0 1"
+		] {
+			assert!(matches!(Position::from_fen(fen), Err(FenError::InvalidEnPassant(_))), "{}", fen);
+		}
+	}
+}
@@ -0,0 +1,41 @@
+//! Chess rules without any rendering: bitboard positions, FEN, moves, legal move
+//! generation, SAN/PGN notation, a simple alpha-beta search to play against, and both sides
+//! of the UCI and CECP (XBoard) engine protocols.
+//!
+//! Squares are addressed by rank and file, both starting at 0, so white's back rank is rank 0
+//! and the a-file is file 0. This matches the `x`/`y` coordinates used on the 3D board.
+
+mod bitboard;
+mod clock;
+mod draw;
+mod eval;
+mod fen;
+mod movegen;
+mod moves;
+mod perft;
+mod pgn;
+mod piece;
+mod position;
+mod process;
+mod san;
+mod search;
+mod square;
+mod uci;
+mod xboard;
+mod zobrist;
+
+pub use bitboard::*;
+pub use clock::*;
+pub use draw::*;
+pub use eval::*;
+pub use fen::*;
+pub use moves::*;
+pub use pgn::*;
+pub use piece::*;
+pub use position::*;
+pub use process::EngineError;
+pub use san::*;
+pub use search::*;
+pub use square::*;
+pub use uci::*;
+pub use xboard::*;
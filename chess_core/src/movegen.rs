@@ -0,0 +1,216 @@
+use crate::{
+	bishop_attacks, castling_rook_files, king_attacks, knight_attacks, pawn_attacks, queen_attacks, rook_attacks, Bitboard, Move, PieceColor, PieceType, Position, Square
+};
+
+const PROMOTION_TYPES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+impl Position {
+	/// Pieces of `by_color` attacking the square, regardless of what's on it.
+	pub fn attackers(&self, square: Square, by_color: PieceColor) -> Bitboard {
+		let occupied = self.occupied();
+		let rooks = self.piece_type(PieceType::Rook) | self.piece_type(PieceType::Queen);
+		let bishops = self.piece_type(PieceType::Bishop) | self.piece_type(PieceType::Queen);
+
+		// Pawns take diagonally forward, so look diagonally backwards from the square
+		let attackers = (pawn_attacks(by_color.opposite(), square) & self.piece_type(PieceType::Pawn))
+			| (knight_attacks(square) & self.piece_type(PieceType::Knight))
+			| (king_attacks(square) & self.piece_type(PieceType::King))
+			| (rook_attacks(square, occupied) & rooks)
+			| (bishop_attacks(square, occupied) & bishops);
+
+		attackers & self.color(by_color)
+	}
+
+	/// Whether any piece of `by_color` attacks the square, regardless of what's on it.
+	pub fn is_square_attacked(&self, square: Square, by_color: PieceColor) -> bool {
+		!self.attackers(square, by_color).is_empty()
+	}
+
+	/// Pieces giving check to the side to move.
+	pub fn checkers(&self) -> Bitboard {
+		let color = self.side_to_move();
+		match self.king_square(color) {
+			Some(square) => self.attackers(square, color.opposite()),
+			None => Bitboard::EMPTY
+		}
+	}
+
+	/// Moves that follow the piece movement rules but may leave the king in check.
+	pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+		let mut moves = Vec::with_capacity(64);
+		let color = self.side_to_move();
+		let occupied = self.occupied();
+		let targets = !self.color(color);
+
+		for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King] {
+			for from in self.pieces_of(color, piece_type) {
+				let attacks = match piece_type {
+					PieceType::Knight => knight_attacks(from),
+					PieceType::Bishop => bishop_attacks(from, occupied),
+					PieceType::Rook => rook_attacks(from, occupied),
+					PieceType::Queen => queen_attacks(from, occupied),
+					_ => king_attacks(from)
+				};
+				moves.extend((attacks & targets).map(|to| Move::new(from, to)));
+
+				if piece_type == PieceType::King {
+					self.castling_moves(from, &mut moves);
+				}
+			}
+		}
+
+		self.pawn_moves(&mut moves);
+
+		moves
+	}
+
+	/// Every move the side to move can play without leaving its own king in check.
+	pub fn legal_moves(&self) -> Vec<Move> {
+		let color = self.side_to_move();
+		let mut position = self.clone();
+
+		self.pseudo_legal_moves()
+			.into_iter()
+			.filter(|mv| {
+				let undo = position.make_move(*mv);
+				let is_legal = !position.is_king_attacked(color);
+				position.unmake_move(*mv, undo);
+
+				is_legal
+			})
+			.collect()
+	}
+
+	pub fn is_legal(&self, mv: Move) -> bool {
+		self.legal_moves().contains(&mv)
+	}
+
+	fn pawn_moves(&self, moves: &mut Vec<Move>) {
+		let color = self.side_to_move();
+		let direction = color.pawn_direction();
+		let start_rank = match color {
+			PieceColor::White => 1,
+			PieceColor::Black => 6
+		};
+		let empty = !self.occupied();
+		let mut enemies = self.color(color.opposite());
+		if let Some(en_passant) = self.en_passant() {
+			enemies |= Bitboard::from_square(en_passant);
+		}
+
+		let mut push = |from: Square, to: Square| {
+			if to.rank() == 0 || to.rank() == 7 {
+				for piece_type in PROMOTION_TYPES {
+					moves.push(Move::with_promotion(from, to, piece_type));
+				}
+			} else {
+				moves.push(Move::new(from, to));
+			}
+		};
+
+		for from in self.pieces_of(color, PieceType::Pawn) {
+			// Normal move, and two squares from the starting rank
+			if let Some(to) = from.offset(direction, 0).filter(|to| empty.contains(*to)) {
+				push(from, to);
+
+				if from.rank() == start_rank {
+					let to = to.offset(direction, 0).unwrap();
+					if empty.contains(to) {
+						push(from, to);
+					}
+				}
+			}
+
+			// Take piece, including en passant
+			for to in pawn_attacks(color, from) & enemies {
+				push(from, to);
+			}
+		}
+	}
+
+	fn castling_moves(&self, from: Square, moves: &mut Vec<Move>) {
+		let color = self.side_to_move();
+		let rank = color.back_rank();
+		if from != Square::new(rank, 4) {
+			return;
+		}
+
+		for king_side in [true, false] {
+			if !self.castling_rights().allows(color, king_side) {
+				continue;
+			}
+
+			// The rook has to still be there with nothing in between
+			let (rook_file, _) = castling_rook_files(king_side);
+			let has_rook = self.pieces_of(color, PieceType::Rook).contains(Square::new(rank, rook_file));
+			let (low, high) = if king_side { (5, 7) } else { (1, 4) };
+			if !has_rook || (low..high).any(|file| self.occupied().contains(Square::new(rank, file))) {
+				continue;
+			}
+
+			// The king can't castle out of or through check, landing in check is caught by the legal move filter
+			let files = if king_side { [4, 5] } else { [4, 3] };
+			if files.iter().any(|file| self.is_square_attacked(Square::new(rank, *file), color.opposite())) {
+				continue;
+			}
+
+			let to_file = if king_side { 6 } else { 2 };
+			moves.push(Move::new(from, Square::new(rank, to_file)));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	fn square(name: &str) -> Square {
+		Square::from_name(name).unwrap()
+	}
+
+	#[test]
+	fn counts_moves_from_the_start() {
+		let mut position = Position::start();
+		assert_eq!(position.legal_moves().len(), 20);
+
+		position.make_move(Move::from_uci("e2e4").unwrap());
+		assert_eq!(position.legal_moves().len(), 20);
+
+		position.make_move(Move::from_uci("e7e5").unwrap());
+		assert_eq!(position.legal_moves().len(), 29);
+	}
+
+	#[test]
+	fn gets_out_of_check() {
+		let position = Position::from_fen("4r2k/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+		assert!(position.is_in_check());
+		assert_eq!(position.checkers(), Bitboard::from_square(square("e8")));
+
+		let mut destinations = position.legal_moves().iter().map(|mv| mv.to.to_string()).collect::<Vec<String>>();
+		destinations.sort();
+		assert_eq!(destinations, ["d1", "d2", "f1", "f2"]);
+
+		// Only the king can move out of a double check, even where a piece could block one of them
+		let position = Position::from_fen("4r2k/8/8/8/8/5n2/8/R3K3 w - - 0 1").unwrap();
+		assert_eq!(position.checkers().count(), 2);
+		assert!(position.legal_moves().iter().all(|mv| mv.from == square("e1")));
+	}
+
+	#[test]
+	fn keeps_pinned_pieces_on_the_pin() {
+		// A pinned bishop can't move at all
+		let position = Position::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+		assert!(!position.is_in_check());
+		assert!(position.legal_moves().iter().all(|mv| mv.from != square("e2")));
+
+		// A pinned rook can still move along the pin, up to taking the pinning piece
+		let position = Position::from_fen("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+		let rook_moves = position.legal_moves().into_iter().filter(|mv| mv.from == square("e2")).collect::<Vec<Move>>();
+		assert_eq!(rook_moves.len(), 5);
+		assert!(rook_moves.iter().all(|mv| mv.to.file() == 4));
+
+		// Taking en passant can't uncover a check along the rank
+		let position = Position::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 2").unwrap();
+		assert!(!position.is_legal(Move::new(square("b5"), square("c6"))));
+	}
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use crate::{PieceType, Square};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move {
+	pub from: Square,
+	pub to: Square,
+	/// Piece a pawn turns into when reaching the last rank.
+	pub promotion: Option<PieceType>
+}
+
+impl Move {
+	pub fn new(from: Square, to: Square) -> Move {
+		Move {
+			from,
+			to,
+			promotion: None
+		}
+	}
+
+	pub fn with_promotion(from: Square, to: Square, promotion: PieceType) -> Move {
+		Move {
+			from,
+			to,
+			promotion: Some(promotion)
+		}
+	}
+
+	/// Reads long algebraic notation as used by UCI, e.g. `e2e4` or `e7e8q`.
+	pub fn from_uci(text: &str) -> Option<Move> {
+		if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+			return None;
+		}
+
+		let from = Square::from_name(&text[0..2])?;
+		let to = Square::from_name(&text[2..4])?;
+
+		match text[4..].chars().next() {
+			None => Some(Move::new(from, to)),
+			Some(c) => match PieceType::from_char(c)? {
+				PieceType::King | PieceType::Pawn => None,
+				piece_type => Some(Move::with_promotion(from, to, piece_type))
+			}
+		}
+	}
+}
+
+/// Long algebraic notation, e.g. `e2e4` or `e7e8q`.
+impl fmt::Display for Move {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}{}", self.from, self.to)?;
+
+		match self.promotion {
+			Some(PieceType::Queen) => write!(f, "q"),
+			Some(PieceType::Rook) => write!(f, "r"),
+			Some(PieceType::Bishop) => write!(f, "b"),
+			Some(PieceType::Knight) => write!(f, "n"),
+			_ => Ok(())
+		}
+	}
+}
@@ -0,0 +1,103 @@
+use crate::{Move, Position};
+
+impl Position {
+	/// Number of move sequences `depth` plies long from this position. Comparing it with known
+	/// counts for reference positions is the usual way of checking move generation.
+	pub fn perft(&self, depth: u32) -> u64 {
+		let mut position = self.clone();
+		position.perft_from(depth)
+	}
+
+	/// [`Position::perft`] split by the first move, to narrow down which line a wrong count comes from.
+	/// There's no first move at depth 0.
+	pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+		if depth == 0 {
+			return Vec::new();
+		}
+
+		let mut position = self.clone();
+
+		self.legal_moves()
+			.into_iter()
+			.map(|mv| {
+				let undo = position.make_move(mv);
+				let nodes = position.perft_from(depth - 1);
+				position.unmake_move(mv, undo);
+
+				(mv, nodes)
+			})
+			.collect()
+	}
+
+	fn perft_from(&mut self, depth: u32) -> u64 {
+		if depth == 0 {
+			return 1;
+		}
+
+		let moves = self.legal_moves();
+		// The moves themselves are the last ply, no need to play them
+		if depth == 1 {
+			return moves.len() as u64;
+		}
+
+		moves
+			.into_iter()
+			.map(|mv| {
+				let undo = self.make_move(mv);
+				let nodes = self.perft_from(depth - 1);
+				self.unmake_move(mv, undo);
+
+				nodes
+			})
+			.sum()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	// Reference positions and their counts from depth 1 on, see https://www.chessprogramming.org/Perft_Results.
+	// The deeper counts take a while in debug builds, they run with `cargo test --release -- --ignored`
+	const POSITIONS: [(&str, &str, [u64; 5]); 6] = [
+		("start", START_FEN, [20, 400, 8_902, 197_281, 4_865_609]),
+		("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", [48, 2_039, 97_862, 4_085_603, 193_690_690]),
+		("position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", [14, 191, 2_812, 43_238, 674_624]),
+		("position 4", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", [6, 264, 9_467, 422_333, 15_833_292]),
+		("position 5", "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", [44, 1_486, 62_379, 2_103_487, 89_941_194]),
+		("position 6", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", [46, 2_079, 89_890, 3_894_594, 164_075_551])
+	];
+
+	fn check_counts(max_nodes: u64) {
+		for (name, fen, counts) in POSITIONS {
+			let position = Position::from_fen(fen).unwrap();
+			for (depth, count) in counts.iter().enumerate().filter(|(_, count)| **count <= max_nodes) {
+				assert_eq!(position.perft(depth as u32 + 1), *count, "{} at depth {}", name, depth + 1);
+			}
+		}
+	}
+
+	#[test]
+	fn counts_reference_positions() {
+		check_counts(100_000);
+	}
+
+	#[test]
+	#[ignore]
+	fn counts_reference_positions_deeper() {
+		check_counts(u64::MAX);
+	}
+
+	#[test]
+	fn divides_by_first_move() {
+		let position = Position::from_fen(POSITIONS[1].1).unwrap();
+		let divide = position.divide(2);
+
+		assert_eq!(divide.len(), 48);
+		assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2_039);
+		// Castling king side, black has 43 answers
+		assert!(divide.contains(&(Move::from_uci("e1g1").unwrap(), 43)));
+		assert_eq!(position.perft(0), 1);
+		assert!(position.divide(0).is_empty());
+	}
+}
@@ -0,0 +1,456 @@
+use std::{error::Error, fmt};
+
+use crate::{FenError, GameResult, Move, PieceColor, Position, SanError, START_FEN};
+
+/// The result token that ends a PGN game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PgnResult {
+	WhiteWins,
+	BlackWins,
+	Draw,
+	/// The game is still going or was abandoned.
+	Unknown
+}
+
+impl PgnResult {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			PgnResult::WhiteWins => "1-0",
+			PgnResult::BlackWins => "0-1",
+			PgnResult::Draw => "1/2-1/2",
+			PgnResult::Unknown => "*"
+		}
+	}
+
+	pub fn from_token(token: &str) -> Option<PgnResult> {
+		match token {
+			"1-0" => Some(PgnResult::WhiteWins),
+			"0-1" => Some(PgnResult::BlackWins),
+			"1/2-1/2" => Some(PgnResult::Draw),
+			"*" => Some(PgnResult::Unknown),
+			_ => None
+		}
+	}
+}
+
+impl From<GameResult> for PgnResult {
+	fn from(result: GameResult) -> Self {
+		match result.winner() {
+			Some(PieceColor::White) => PgnResult::WhiteWins,
+			Some(PieceColor::Black) => PgnResult::BlackWins,
+			None => PgnResult::Draw
+		}
+	}
+}
+
+impl fmt::Display for PgnResult {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+/// One game in Portable Game Notation: its tags and the moves played from `start`.
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+	tags: Vec<(String, String)>,
+	pub start: Position,
+	pub moves: Vec<Move>,
+	pub result: PgnResult
+}
+
+impl PgnGame {
+	/// A game with the Seven Tag Roster filled in with unknown values.
+	pub fn new(start: Position, moves: Vec<Move>, result: PgnResult) -> PgnGame {
+		let mut game = PgnGame {
+			tags: Vec::new(),
+			start,
+			moves,
+			result
+		};
+
+		for (name, value) in [
+			("Event", "?"),
+			("Site", "?"),
+			("Date", "????.??.??"),
+			("Round", "?"),
+			("White", "?"),
+			("Black", "?")
+		] {
+			game.set_tag(name, value);
+		}
+
+		game
+	}
+
+	pub fn tag(&self, name: &str) -> Option<&str> {
+		self.tags
+			.iter()
+			.find(|(tag, _)| tag == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Sets a tag, keeping its place if it already exists.
+	pub fn set_tag(&mut self, name: &str, value: &str) {
+		match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+			Some((_, old_value)) => *old_value = value.to_string(),
+			None => self.tags.push((name.to_string(), value.to_string()))
+		}
+	}
+
+	pub fn tags(&self) -> &[(String, String)] {
+		&self.tags
+	}
+
+	/// The game as PGN text, with movetext wrapped below 80 columns.
+	pub fn to_pgn(&self) -> String {
+		let mut pgn = String::new();
+
+		// Result is part of the Seven Tag Roster but always follows the game's result
+		let mut tags = self.tags.clone();
+		tags.retain(|(name, _)| name != "Result" && name != "SetUp" && name != "FEN");
+		tags.insert(6.min(tags.len()), ("Result".to_string(), self.result.to_string()));
+
+		let fen = self.start.to_fen();
+		if fen != START_FEN {
+			tags.push(("SetUp".to_string(), "1".to_string()));
+			tags.push(("FEN".to_string(), fen));
+		}
+
+		for (name, value) in tags {
+			pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+		}
+		pgn.push('\n');
+
+		let mut tokens = Vec::new();
+		let mut position = self.start.clone();
+		for (i, (mv, san)) in self.moves.iter().zip(self.start.san_line(&self.moves)).enumerate() {
+			if position.side_to_move() == PieceColor::White {
+				tokens.push(format!("{}.", position.fullmove_number()));
+			} else if i == 0 {
+				tokens.push(format!("{}...", position.fullmove_number()));
+			}
+
+			tokens.push(san);
+			position.make_move(*mv);
+		}
+		tokens.push(self.result.to_string());
+
+		let mut line_length = 0;
+		for token in tokens {
+			if line_length > 0 && line_length + 1 + token.len() >= 80 {
+				pgn.push('\n');
+				line_length = 0;
+			} else if line_length > 0 {
+				pgn.push(' ');
+				line_length += 1;
+			}
+
+			line_length += token.len();
+			pgn.push_str(&token);
+		}
+		pgn.push_str("\n\n");
+
+		pgn
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+	/// A tag pair that isn't `[Name "value"]`.
+	InvalidTag(String),
+	/// A comment, variation or tag that never ends.
+	Unterminated(&'static str),
+	InvalidFen(FenError),
+	/// A move that can't be played, counting games and plies from 1.
+	InvalidMove {
+		game: usize,
+		ply: usize,
+		error: SanError
+	}
+}
+
+impl fmt::Display for PgnError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PgnError::InvalidTag(tag) => write!(f, "invalid tag '{}'", tag),
+			PgnError::Unterminated(what) => write!(f, "unterminated {}", what),
+			PgnError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+			PgnError::InvalidMove { game, ply, error } => write!(f, "game {}, ply {}: {}", game, ply, error)
+		}
+	}
+}
+
+impl Error for PgnError {}
+
+// A game being read, its start position is only known once all the tags are in
+#[derive(Default)]
+struct GameReader {
+	tags: Vec<(String, String)>,
+	position: Option<(Position, Position)>,
+	moves: Vec<Move>
+}
+
+impl GameReader {
+	fn is_empty(&self) -> bool {
+		self.tags.is_empty() && self.position.is_none()
+	}
+
+	fn has_moves(&self) -> bool {
+		self.position.is_some()
+	}
+
+	// The (start, current) positions, set up from the FEN tag on the first move
+	fn positions(&mut self) -> Result<&mut (Position, Position), PgnError> {
+		if self.position.is_none() {
+			let start = match self.tags.iter().find(|(name, _)| name == "FEN") {
+				Some((_, fen)) => Position::from_fen(fen).map_err(PgnError::InvalidFen)?,
+				None => Position::start()
+			};
+			self.position = Some((start.clone(), start));
+		}
+
+		Ok(self.position.as_mut().unwrap())
+	}
+
+	fn finish(mut self, result: PgnResult) -> Result<PgnGame, PgnError> {
+		let start = self.positions()?.0.clone();
+		let mut game = PgnGame::new(start, self.moves, result);
+		for (name, value) in &self.tags {
+			game.set_tag(name, value);
+		}
+
+		Ok(game)
+	}
+}
+
+/// Reads every game in a PGN file. Comments, NAGs and variations are skipped, only the main line is kept.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+	let mut games = Vec::new();
+	let mut reader = GameReader::default();
+	let chars = text.chars().collect::<Vec<char>>();
+	let mut i = 0;
+
+	while i < chars.len() {
+		let c = chars[i];
+
+		// Lines starting with % are escaped
+		if c == '%' && (i == 0 || chars[i - 1] == '\n') {
+			i = skip_until(&chars, i, '\n').unwrap_or(chars.len());
+			continue;
+		}
+
+		match c {
+			c if c.is_whitespace() => i += 1,
+			'[' => {
+				// Tags after moves start a new game, even without a result
+				if reader.has_moves() {
+					games.push(std::mem::take(&mut reader).finish(PgnResult::Unknown)?);
+				}
+
+				let end = tag_end(&chars, i).ok_or(PgnError::Unterminated("tag"))?;
+				reader.tags.push(parse_tag(&chars[i + 1..end - 1].iter().collect::<String>())?);
+				i = end;
+			},
+			'{' => i = skip_until(&chars, i, '}').ok_or(PgnError::Unterminated("comment"))?,
+			';' => i = skip_until(&chars, i, '\n').unwrap_or(chars.len()),
+			'(' => i = skip_variation(&chars, i)?,
+			')' => i += 1,
+			_ => {
+				let start = i;
+				while i < chars.len() && !chars[i].is_whitespace() && !"[]{}();".contains(chars[i]) {
+					i += 1;
+				}
+				let token = chars[start..i].iter().collect::<String>();
+
+				if let Some(result) = PgnResult::from_token(&token) {
+					games.push(std::mem::take(&mut reader).finish(result)?);
+					continue;
+				}
+
+				// Numeric annotation glyphs, e.g. $1
+				if token.starts_with('$') {
+					continue;
+				}
+
+				// Move numbers can be glued to the move, as in 12.e4
+				let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+				if san.is_empty() {
+					continue;
+				}
+
+				let ply = reader.moves.len() + 1;
+				let (_, position) = reader.positions()?;
+				let mv = position.parse_san(san).map_err(|error| PgnError::InvalidMove {
+					game: games.len() + 1,
+					ply,
+					error
+				})?;
+				position.make_move(mv);
+				reader.moves.push(mv);
+			}
+		}
+	}
+
+	if !reader.is_empty() {
+		games.push(reader.finish(PgnResult::Unknown)?);
+	}
+
+	Ok(games)
+}
+
+// Index just past the next `end` character after `start`
+fn skip_until(chars: &[char], start: usize, end: char) -> Option<usize> {
+	chars[start + 1..]
+		.iter()
+		.position(|c| *c == end)
+		.map(|offset| start + offset + 2)
+}
+
+// Index just past the bracket closing the tag at `start`, after its quoted value, which can hold
+// brackets and escaped quotes of its own
+fn tag_end(chars: &[char], start: usize) -> Option<usize> {
+	let mut i = start + 1;
+
+	// Name and value up to the opening quote
+	while chars.get(i).is_some_and(|c| *c != '"' && *c != ']') {
+		i += 1;
+	}
+
+	if chars.get(i) == Some(&'"') {
+		i += 1;
+		loop {
+			match chars.get(i)? {
+				'\\' => i += 2,
+				'"' => break,
+				_ => i += 1
+			}
+		}
+		i = skip_until(chars, i, ']')?;
+	} else {
+		i += 1;
+	}
+
+	Some(i)
+}
+
+// Index just past the parenthesis closing the variation at `start`, which can hold comments and other variations
+fn skip_variation(chars: &[char], start: usize) -> Result<usize, PgnError> {
+	let mut depth = 0;
+	let mut i = start;
+
+	while i < chars.len() {
+		match chars[i] {
+			'(' => depth += 1,
+			')' => {
+				depth -= 1;
+				if depth == 0 {
+					return Ok(i + 1);
+				}
+			},
+			'{' => {
+				i = skip_until(chars, i, '}').ok_or(PgnError::Unterminated("comment"))?;
+				continue;
+			},
+			_ => {}
+		}
+		i += 1;
+	}
+
+	Err(PgnError::Unterminated("variation"))
+}
+
+fn parse_tag(tag: &str) -> Result<(String, String), PgnError> {
+	let invalid = || PgnError::InvalidTag(tag.to_string());
+	let tag = tag.trim();
+	let (name, value) = tag.split_once(char::is_whitespace).ok_or_else(invalid)?;
+	let value = value.trim();
+
+	if !value.starts_with('"') || !value.ends_with('"') || value.len() < 2 {
+		return Err(invalid());
+	}
+
+	let value = value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\");
+	Ok((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	fn uci_moves(game: &PgnGame) -> Vec<String> {
+		game.moves.iter().map(|mv| mv.to_string()).collect()
+	}
+
+	#[test]
+	fn reads_tags() {
+		let games = parse_pgn("[Event \"Club [blitz] \\\"final\\\"\"]\n[Site \"Here\"]\n\n1. e4 *\n").unwrap();
+		assert_eq!(games.len(), 1);
+		assert_eq!(games[0].tag("Event"), Some("Club [blitz] \"final\""));
+		assert_eq!(games[0].tag("Site"), Some("Here"));
+
+		assert_eq!(parse_pgn("[Event \"x\"").unwrap_err(), PgnError::Unterminated("tag"));
+		assert_eq!(parse_pgn("[Event \"x]").unwrap_err(), PgnError::Unterminated("tag"));
+		assert!(matches!(parse_pgn("[Event x]"), Err(PgnError::InvalidTag(_))));
+	}
+
+	#[test]
+	fn reads_several_games() {
+		let text = "[Event \"One\"]\n\n1. e4 e5 1-0\n\n[Event \"Two\"]\n\n1. d4 d5 2. c4 0-1\n\n[Event \"Three\"]\n\n1. Nf3\n";
+		let games = parse_pgn(text).unwrap();
+
+		assert_eq!(games.len(), 3);
+		assert_eq!(games[0].tag("Event"), Some("One"));
+		assert_eq!(uci_moves(&games[0]), ["e2e4", "e7e5"]);
+		assert_eq!(games[0].result, PgnResult::WhiteWins);
+		assert_eq!(uci_moves(&games[1]), ["d2d4", "d7d5", "c2c4"]);
+		assert_eq!(games[1].result, PgnResult::BlackWins);
+		assert_eq!(uci_moves(&games[2]), ["g1f3"]);
+		assert_eq!(games[2].result, PgnResult::Unknown);
+	}
+
+	#[test]
+	fn skips_comments_nags_and_variations() {
+		let text = "% escaped line\n1. e4 {best by test} e5 $1 2. Nf3 ; rest of the line 2. d4\n\
+			(2. f4 exf4 (2... d5 {the counter (gambit)} 3. exd5) 3. Nf3) 2... Nc6 $14 3.Bb5 1/2-1/2";
+		let games = parse_pgn(text).unwrap();
+
+		assert_eq!(games.len(), 1);
+		assert_eq!(uci_moves(&games[0]), ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+		assert_eq!(games[0].result, PgnResult::Draw);
+
+		assert_eq!(parse_pgn("1. e4 {never closed").unwrap_err(), PgnError::Unterminated("comment"));
+		assert_eq!(parse_pgn("1. e4 (1. d4 (1. c4)").unwrap_err(), PgnError::Unterminated("variation"));
+	}
+
+	#[test]
+	fn reads_games_from_a_position() {
+		let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40";
+		let text = format!("[SetUp \"1\"]\n[FEN \"{}\"]\n\n40... Kd7 41. e4 *\n", fen);
+		let games = parse_pgn(&text).unwrap();
+		assert_eq!(games[0].start.to_fen(), fen);
+		assert_eq!(uci_moves(&games[0]), ["e8d7", "e2e4"]);
+
+		assert!(matches!(parse_pgn("1. e4 e5 2. Ke3 *"), Err(PgnError::InvalidMove { game: 1, ply: 3, .. })));
+	}
+
+	#[test]
+	fn round_trips_games() {
+		let mut position = Position::start();
+		let mut moves = Vec::new();
+		for uci in ["e2e4", "c7c5", "g1f3", "d7d6", "d2d4", "c5d4", "f3d4", "g8f6", "b1c3", "a7a6"] {
+			let mv = Move::from_uci(uci).unwrap();
+			position.make_move(mv);
+			moves.push(mv);
+		}
+
+		let mut game = PgnGame::new(Position::start(), moves.clone(), PgnResult::Draw);
+		game.set_tag("White", "A \"quoted\" name");
+		let games = parse_pgn(&game.to_pgn()).unwrap();
+
+		assert_eq!(games.len(), 1);
+		assert_eq!(games[0].moves, moves);
+		assert_eq!(games[0].result, PgnResult::Draw);
+		assert_eq!(games[0].tag("White"), Some("A \"quoted\" name"));
+		assert_eq!(games[0].tag("Result"), Some("1/2-1/2"));
+	}
+}
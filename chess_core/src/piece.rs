@@ -0,0 +1,119 @@
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceColor {
+	White,
+	Black
+}
+
+impl PieceColor {
+	pub fn opposite(&self) -> PieceColor {
+		match self {
+			PieceColor::White => PieceColor::Black,
+			PieceColor::Black => PieceColor::White
+		}
+	}
+
+	/// Rank where this color's king and rooks start.
+	pub fn back_rank(&self) -> u8 {
+		match self {
+			PieceColor::White => 0,
+			PieceColor::Black => 7
+		}
+	}
+
+	/// Rank direction this color's pawns move in.
+	pub fn pawn_direction(&self) -> i8 {
+		match self {
+			PieceColor::White => 1,
+			PieceColor::Black => -1
+		}
+	}
+
+	/// 0 for white and 1 for black, for tables indexed by color.
+	pub fn index(&self) -> usize {
+		*self as usize
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceType {
+	King,
+	Queen,
+	Bishop,
+	Knight,
+	Rook,
+	Pawn
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Piece {
+	pub color: PieceColor,
+	pub piece_type: PieceType
+}
+
+impl Piece {
+	pub fn new(color: PieceColor, piece_type: PieceType) -> Piece {
+		Piece {
+			color,
+			piece_type
+		}
+	}
+
+	/// FEN letter, uppercase for white and lowercase for black.
+	pub fn to_char(&self) -> char {
+		match self.color {
+			PieceColor::White => self.piece_type.to_char(),
+			PieceColor::Black => self.piece_type.to_char().to_ascii_lowercase()
+		}
+	}
+
+	pub fn from_char(c: char) -> Option<Piece> {
+		let color = if c.is_ascii_uppercase() {
+			PieceColor::White
+		} else {
+			PieceColor::Black
+		};
+
+		PieceType::from_char(c).map(|piece_type| Piece::new(color, piece_type))
+	}
+}
+
+impl PieceType {
+	pub const ALL: [PieceType; 6] = [
+		PieceType::King,
+		PieceType::Queen,
+		PieceType::Bishop,
+		PieceType::Knight,
+		PieceType::Rook,
+		PieceType::Pawn
+	];
+
+	/// Position in [`PieceType::ALL`], for tables indexed by piece type.
+	pub fn index(&self) -> usize {
+		*self as usize
+	}
+
+	/// Uppercase letter used for the piece in FEN and SAN.
+	pub fn to_char(&self) -> char {
+		match self {
+			PieceType::King => 'K',
+			PieceType::Queen => 'Q',
+			PieceType::Bishop => 'B',
+			PieceType::Knight => 'N',
+			PieceType::Rook => 'R',
+			PieceType::Pawn => 'P'
+		}
+	}
+
+	/// Reads a piece letter in either case.
+	pub fn from_char(c: char) -> Option<PieceType> {
+		match c.to_ascii_uppercase() {
+			'K' => Some(PieceType::King),
+			'Q' => Some(PieceType::Queen),
+			'B' => Some(PieceType::Bishop),
+			'N' => Some(PieceType::Knight),
+			'R' => Some(PieceType::Rook),
+			'P' => Some(PieceType::Pawn),
+			_ => None
+		}
+	}
+}
@@ -0,0 +1,523 @@
+use std::fmt;
+
+use crate::{
+	pawn_attacks,
+	zobrist::{black_to_move_key, castling_key, en_passant_key, piece_key},
+	Bitboard, DrawReason, Move, Piece, PieceColor, PieceType, Square
+};
+
+/// Which sides each player may still castle to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CastlingRights {
+	pub white_king_side: bool,
+	pub white_queen_side: bool,
+	pub black_king_side: bool,
+	pub black_queen_side: bool
+}
+
+impl Default for CastlingRights {
+	fn default() -> Self {
+		Self {
+			white_king_side: true,
+			white_queen_side: true,
+			black_king_side: true,
+			black_queen_side: true
+		}
+	}
+}
+
+impl CastlingRights {
+	pub fn none() -> Self {
+		Self {
+			white_king_side: false,
+			white_queen_side: false,
+			black_king_side: false,
+			black_queen_side: false
+		}
+	}
+
+	pub fn allows(&self, color: PieceColor, king_side: bool) -> bool {
+		match (color, king_side) {
+			(PieceColor::White, true) => self.white_king_side,
+			(PieceColor::White, false) => self.white_queen_side,
+			(PieceColor::Black, true) => self.black_king_side,
+			(PieceColor::Black, false) => self.black_queen_side
+		}
+	}
+
+	/// Any move from or onto a king or rook starting square loses the rights tied to it,
+	/// which covers the king moving, the rook moving and the rook being taken.
+	pub fn update(&mut self, from: Square, to: Square) {
+		for square in [from, to] {
+			match (square.rank(), square.file()) {
+				(0, 4) => {
+					self.white_king_side = false;
+					self.white_queen_side = false;
+				},
+				(0, 7) => self.white_king_side = false,
+				(0, 0) => self.white_queen_side = false,
+				(7, 4) => {
+					self.black_king_side = false;
+					self.black_queen_side = false;
+				},
+				(7, 7) => self.black_king_side = false,
+				(7, 0) => self.black_queen_side = false,
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Rook files (from, to) when castling towards the king or queen side.
+pub fn castling_rook_files(king_side: bool) -> (u8, u8) {
+	if king_side {
+		(7, 5)
+	} else {
+		(0, 3)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+	Checkmate { winner: PieceColor },
+	/// The loser gave up.
+	Resignation { winner: PieceColor },
+	/// The loser ran out of time.
+	Timeout { winner: PieceColor },
+	Stalemate,
+	Draw(DrawReason)
+}
+
+impl GameResult {
+	/// The color that won, None for a draw.
+	pub fn winner(&self) -> Option<PieceColor> {
+		match self {
+			GameResult::Checkmate { winner } | GameResult::Resignation { winner } | GameResult::Timeout { winner } => Some(*winner),
+			GameResult::Stalemate | GameResult::Draw(_) => None
+		}
+	}
+}
+
+impl fmt::Display for GameResult {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let winner = match self.winner() {
+			Some(PieceColor::White) => "White",
+			Some(PieceColor::Black) => "Black",
+			None => ""
+		};
+
+		match self {
+			GameResult::Checkmate { .. } => write!(f, "{} wins by checkmate", winner),
+			GameResult::Resignation { .. } => write!(f, "{} wins by resignation", winner),
+			GameResult::Timeout { .. } => write!(f, "{} wins on time", winner),
+			GameResult::Stalemate => write!(f, "Draw by stalemate"),
+			GameResult::Draw(reason) => write!(f, "Draw by {}", reason)
+		}
+	}
+}
+
+/// State a move overwrites, needed to take it back with [`Position::unmake_move`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+	captured: Option<(Square, Piece)>,
+	castling_rights: CastlingRights,
+	en_passant: Option<Square>,
+	halfmove_clock: u32
+}
+
+impl Undo {
+	/// The piece the move took and the square it was on.
+	pub fn captured(&self) -> Option<(Square, Piece)> {
+		self.captured
+	}
+}
+
+/// Everything needed to know which moves are legal: the pieces, whose turn it is,
+/// castling rights and the en passant square, plus the move counters.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Position {
+	board: [Option<Piece>; 64],
+	// The same pieces as bitboards, by color and by piece type
+	colors: [Bitboard; 2],
+	piece_types: [Bitboard; 6],
+	side_to_move: PieceColor,
+	castling_rights: CastlingRights,
+	en_passant: Option<Square>,
+	halfmove_clock: u32,
+	fullmove_number: u32,
+	// Zobrist hash of everything but the en passant square, kept up to date by every change
+	hash: u64
+}
+
+impl Default for Position {
+	fn default() -> Self {
+		Self::start()
+	}
+}
+
+impl Position {
+	/// A board without any pieces, white to move.
+	pub fn empty() -> Position {
+		Position {
+			board: [None; 64],
+			colors: [Bitboard::EMPTY; 2],
+			piece_types: [Bitboard::EMPTY; 6],
+			side_to_move: PieceColor::White,
+			castling_rights: CastlingRights::none(),
+			en_passant: None,
+			halfmove_clock: 0,
+			fullmove_number: 1,
+			hash: 0
+		}
+	}
+
+	/// The standard starting position.
+	pub fn start() -> Position {
+		let back_row = [
+			PieceType::Rook,
+			PieceType::Knight,
+			PieceType::Bishop,
+			PieceType::Queen,
+			PieceType::King,
+			PieceType::Bishop,
+			PieceType::Knight,
+			PieceType::Rook
+		];
+
+		let mut position = Position::empty();
+		for (file, piece_type) in back_row.iter().enumerate() {
+			let file = file as u8;
+			position.set_piece(Square::new(0, file), Some(Piece::new(PieceColor::White, *piece_type)));
+			position.set_piece(Square::new(1, file), Some(Piece::new(PieceColor::White, PieceType::Pawn)));
+			position.set_piece(Square::new(6, file), Some(Piece::new(PieceColor::Black, PieceType::Pawn)));
+			position.set_piece(Square::new(7, file), Some(Piece::new(PieceColor::Black, *piece_type)));
+		}
+		position.set_castling_rights(CastlingRights::default());
+
+		position
+	}
+
+	pub fn piece_at(&self, square: Square) -> Option<Piece> {
+		self.board[square.index()]
+	}
+
+	pub fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
+		let bit = Bitboard::from_square(square);
+		if let Some(old) = self.board[square.index()] {
+			self.colors[old.color.index()] ^= bit;
+			self.piece_types[old.piece_type.index()] ^= bit;
+			self.hash ^= piece_key(old.color, old.piece_type, square);
+		}
+		if let Some(new) = piece {
+			self.colors[new.color.index()] |= bit;
+			self.piece_types[new.piece_type.index()] |= bit;
+			self.hash ^= piece_key(new.color, new.piece_type, square);
+		}
+
+		self.board[square.index()] = piece;
+	}
+
+	/// Every piece on the board along with its square.
+	pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+		self.occupied().map(move |square| (square, self.board[square.index()].unwrap()))
+	}
+
+	/// Squares with a piece of either color on them.
+	pub fn occupied(&self) -> Bitboard {
+		self.colors[0] | self.colors[1]
+	}
+
+	/// Squares with a piece of the color on them.
+	pub fn color(&self, color: PieceColor) -> Bitboard {
+		self.colors[color.index()]
+	}
+
+	/// Squares with a piece of the type on them, of either color.
+	pub fn piece_type(&self, piece_type: PieceType) -> Bitboard {
+		self.piece_types[piece_type.index()]
+	}
+
+	pub fn pieces_of(&self, color: PieceColor, piece_type: PieceType) -> Bitboard {
+		self.colors[color.index()] & self.piece_types[piece_type.index()]
+	}
+
+	pub fn side_to_move(&self) -> PieceColor {
+		self.side_to_move
+	}
+
+	pub fn set_side_to_move(&mut self, color: PieceColor) {
+		if color != self.side_to_move {
+			self.switch_side();
+		}
+	}
+
+	fn switch_side(&mut self) {
+		self.side_to_move = self.side_to_move.opposite();
+		self.hash ^= black_to_move_key();
+	}
+
+	pub fn castling_rights(&self) -> CastlingRights {
+		self.castling_rights
+	}
+
+	pub fn set_castling_rights(&mut self, castling_rights: CastlingRights) {
+		self.hash ^= castling_key(self.castling_rights) ^ castling_key(castling_rights);
+		self.castling_rights = castling_rights;
+	}
+
+	/// Square a pawn skipped over with a two square move on the previous turn.
+	pub fn en_passant(&self) -> Option<Square> {
+		self.en_passant
+	}
+
+	pub fn set_en_passant(&mut self, en_passant: Option<Square>) {
+		self.en_passant = en_passant;
+	}
+
+	/// Half moves since the last capture or pawn move.
+	pub fn halfmove_clock(&self) -> u32 {
+		self.halfmove_clock
+	}
+
+	pub fn set_halfmove_clock(&mut self, halfmove_clock: u32) {
+		self.halfmove_clock = halfmove_clock;
+	}
+
+	/// Starts at 1 and goes up after every black move.
+	pub fn fullmove_number(&self) -> u32 {
+		self.fullmove_number
+	}
+
+	pub fn set_fullmove_number(&mut self, fullmove_number: u32) {
+		self.fullmove_number = fullmove_number;
+	}
+
+	/// 64-bit Zobrist hash of the pieces, side to move, castling rights and en passant file,
+	/// for telling positions apart quickly. Positions that count as the same for repetitions
+	/// hash the same, so the en passant file only counts when a pawn can actually take en passant.
+	pub fn zobrist_hash(&self) -> u64 {
+		let en_passant = self.en_passant.filter(|square| {
+			let color = self.side_to_move;
+			!(pawn_attacks(color.opposite(), *square) & self.pieces_of(color, PieceType::Pawn)).is_empty()
+		});
+
+		match en_passant {
+			Some(square) => self.hash ^ en_passant_key(square.file()),
+			None => self.hash
+		}
+	}
+
+	pub fn king_square(&self, color: PieceColor) -> Option<Square> {
+		self.pieces_of(color, PieceType::King).first()
+	}
+
+	/// Whether the side to move has its king attacked.
+	pub fn is_in_check(&self) -> bool {
+		self.is_king_attacked(self.side_to_move)
+	}
+
+	pub(crate) fn is_king_attacked(&self, color: PieceColor) -> bool {
+		match self.king_square(color) {
+			Some(square) => self.is_square_attacked(square, color.opposite()),
+			None => false
+		}
+	}
+
+	pub fn is_castling(&self, mv: Move) -> bool {
+		matches!(self.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::King)
+			&& (mv.from.file() as i8 - mv.to.file() as i8).abs() == 2
+	}
+
+	/// The rook's (from, to) squares if the move castles.
+	pub fn castling_rook_move(&self, mv: Move) -> Option<(Square, Square)> {
+		if !self.is_castling(mv) {
+			return None;
+		}
+
+		let (rook_from, rook_to) = castling_rook_files(mv.to.file() > mv.from.file());
+		Some((Square::new(mv.from.rank(), rook_from), Square::new(mv.from.rank(), rook_to)))
+	}
+
+	/// A pawn taking diagonally onto the en passant square.
+	pub fn is_en_passant(&self, mv: Move) -> bool {
+		matches!(self.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::Pawn)
+			&& mv.from.file() != mv.to.file()
+			&& Some(mv.to) == self.en_passant
+	}
+
+	/// Square of the piece the move takes, which isn't the target square for en passant.
+	pub fn captured_square(&self, mv: Move) -> Option<Square> {
+		if self.is_en_passant(mv) {
+			Some(Square::new(mv.from.rank(), mv.to.file()))
+		} else if self.piece_at(mv.to).is_some() {
+			Some(mv.to)
+		} else {
+			None
+		}
+	}
+
+	pub fn is_capture(&self, mv: Move) -> bool {
+		self.captured_square(mv).is_some()
+	}
+
+	/// Whether the move takes a pawn to the last rank, whatever piece it's promoted to.
+	pub fn is_promotion(&self, mv: Move) -> bool {
+		matches!(self.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::Pawn)
+			&& (mv.to.rank() == 0 || mv.to.rank() == 7)
+	}
+
+	/// Plays a move without checking it, returning what's needed to take it back.
+	pub fn make_move(&mut self, mv: Move) -> Undo {
+		let piece = self.piece_at(mv.from).expect("no piece to move");
+
+		let captured = self.captured_square(mv).map(|square| {
+			let captured = self.piece_at(square).unwrap();
+			self.set_piece(square, None);
+			(square, captured)
+		});
+
+		let undo = Undo {
+			captured,
+			castling_rights: self.castling_rights,
+			en_passant: self.en_passant,
+			halfmove_clock: self.halfmove_clock
+		};
+
+		// The rook jumps over the king when castling
+		if let Some((rook_from, rook_to)) = self.castling_rook_move(mv) {
+			let rook = self.piece_at(rook_from);
+			self.set_piece(rook_from, None);
+			self.set_piece(rook_to, rook);
+		}
+
+		self.set_piece(mv.from, None);
+		self.set_piece(mv.to, Some(match mv.promotion {
+			Some(piece_type) => Piece::new(piece.color, piece_type),
+			None => piece
+		}));
+
+		let mut castling_rights = self.castling_rights;
+		castling_rights.update(mv.from, mv.to);
+		self.set_castling_rights(castling_rights);
+
+		self.en_passant = if piece.piece_type == PieceType::Pawn && (mv.from.rank() as i8 - mv.to.rank() as i8).abs() == 2 {
+			Some(Square::new((mv.from.rank() + mv.to.rank()) / 2, mv.from.file()))
+		} else {
+			None
+		};
+
+		if piece.piece_type == PieceType::Pawn || captured.is_some() {
+			self.halfmove_clock = 0;
+		} else {
+			self.halfmove_clock += 1;
+		}
+
+		if self.side_to_move == PieceColor::Black {
+			self.fullmove_number += 1;
+		}
+		self.switch_side();
+
+		undo
+	}
+
+	/// Takes back a move played with [`Position::make_move`].
+	pub fn unmake_move(&mut self, mv: Move, undo: Undo) {
+		self.switch_side();
+		if self.side_to_move == PieceColor::Black {
+			self.fullmove_number -= 1;
+		}
+
+		let piece = self.piece_at(mv.to).expect("no piece to move back");
+		let piece = if mv.promotion.is_some() {
+			Piece::new(piece.color, PieceType::Pawn)
+		} else {
+			piece
+		};
+
+		self.set_piece(mv.to, None);
+		self.set_piece(mv.from, Some(piece));
+
+		if let Some((rook_from, rook_to)) = self.castling_rook_move(mv) {
+			let rook = self.piece_at(rook_to);
+			self.set_piece(rook_to, None);
+			self.set_piece(rook_from, rook);
+		}
+
+		if let Some((square, captured)) = undo.captured {
+			self.set_piece(square, Some(captured));
+		}
+
+		self.set_castling_rights(undo.castling_rights);
+		self.en_passant = undo.en_passant;
+		self.halfmove_clock = undo.halfmove_clock;
+	}
+
+	/// A copy of the position with the move played.
+	pub fn play(&self, mv: Move) -> Position {
+		let mut position = self.clone();
+		position.make_move(mv);
+		position
+	}
+
+	/// How the game ended if the side to move has no legal moves, None if it goes on.
+	pub fn game_result(&self) -> Option<GameResult> {
+		if !self.legal_moves().is_empty() {
+			return None;
+		}
+
+		if self.is_in_check() {
+			Some(GameResult::Checkmate {
+				winner: self.side_to_move.opposite()
+			})
+		} else {
+			Some(GameResult::Stalemate)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	#[test]
+	fn round_trips_fen() {
+		for fen in [
+			"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+			"rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+			"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+			"8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+			"4k3/8/8/8/8/8/8/4K3 b - - 12 40"
+		] {
+			assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+		}
+		assert_eq!(Position::start().to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+	}
+
+	#[test]
+	fn unmakes_every_move() {
+		let position = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+		let mut played = position.clone();
+
+		for mv in position.legal_moves() {
+			let undo = played.make_move(mv);
+			assert_ne!(played, position, "{}", mv);
+			played.unmake_move(mv, undo);
+			assert_eq!(played, position, "{}", mv);
+		}
+	}
+
+	#[test]
+	fn tracks_moves() {
+		let mut position = Position::start();
+		position.make_move(Move::from_uci("e2e4").unwrap());
+
+		assert_eq!(position.side_to_move(), PieceColor::Black);
+		assert_eq!(position.en_passant(), Square::from_name("e3"));
+		assert_eq!(position.piece_at(Square::from_name("e4").unwrap()), Some(Piece::new(PieceColor::White, PieceType::Pawn)));
+		assert_eq!(position.piece_at(Square::from_name("e2").unwrap()), None);
+
+		position.make_move(Move::from_uci("g8f6").unwrap());
+		assert_eq!(position.fullmove_number(), 2);
+		assert_eq!(position.halfmove_clock(), 1);
+	}
+}
@@ -0,0 +1,214 @@
+use std::{
+	error::Error,
+	fmt,
+	io::{self, BufRead, BufReader, Read, Write},
+	process::{Child, Command, Stdio},
+	sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+	thread,
+	time::{Duration, Instant}
+};
+
+use crate::FenError;
+
+#[derive(Debug)]
+pub enum EngineError {
+	Io(io::Error),
+	/// The engine didn't answer a command in time.
+	Timeout(&'static str),
+	/// The engine closed its output, usually because it quit or crashed.
+	Disconnected,
+	/// A command sent to the engine that it doesn't understand.
+	InvalidCommand(String),
+	InvalidFen(FenError)
+}
+
+impl fmt::Display for EngineError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			EngineError::Io(error) => write!(f, "{}", error),
+			EngineError::Timeout(command) => write!(f, "the engine didn't answer '{}' in time", command),
+			EngineError::Disconnected => write!(f, "the engine stopped responding"),
+			EngineError::InvalidCommand(command) => write!(f, "unknown command '{}'", command),
+			EngineError::InvalidFen(error) => write!(f, "invalid position: {}", error)
+		}
+	}
+}
+
+impl Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+	fn from(error: io::Error) -> Self {
+		EngineError::Io(error)
+	}
+}
+
+// An engine talking a line based protocol, usually a child process. Its output is read on a
+// separate thread and parsed into messages there, so they can be polled without blocking
+pub(crate) struct EngineProcess<M> {
+	writer: Box<dyn Write + Send>,
+	messages: Receiver<M>,
+	child: Option<Child>
+}
+
+impl<M: Send + 'static> EngineProcess<M> {
+	pub(crate) fn spawn(path: &str, parse: fn(&str) -> M) -> Result<EngineProcess<M>, EngineError> {
+		let mut child = Command::new(path)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()?;
+
+		let stdin = child.stdin.take().ok_or(EngineError::Disconnected)?;
+		let stdout = child.stdout.take().ok_or(EngineError::Disconnected)?;
+
+		let mut process = EngineProcess::new(stdout, stdin, parse);
+		process.child = Some(child);
+		Ok(process)
+	}
+
+	pub(crate) fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static, parse: fn(&str) -> M) -> EngineProcess<M> {
+		let (sender, messages) = mpsc::channel();
+
+		thread::spawn(move || {
+			for line in BufReader::new(reader).lines() {
+				let message = match line {
+					Ok(line) => parse(&line),
+					Err(_) => break
+				};
+
+				if sender.send(message).is_err() {
+					break;
+				}
+			}
+		});
+
+		EngineProcess {
+			writer: Box::new(writer),
+			messages,
+			child: None
+		}
+	}
+
+	pub(crate) fn send(&mut self, command: &str) -> Result<(), EngineError> {
+		writeln!(self.writer, "{}", command)?;
+		self.writer.flush()?;
+		Ok(())
+	}
+
+	pub(crate) fn try_recv(&self) -> Result<Option<M>, EngineError> {
+		match self.messages.try_recv() {
+			Ok(message) => Ok(Some(message)),
+			Err(TryRecvError::Empty) => Ok(None),
+			Err(TryRecvError::Disconnected) => Err(EngineError::Disconnected)
+		}
+	}
+
+	// `command` is what the engine is expected to be answering, for the timeout error
+	pub(crate) fn recv_timeout(&self, timeout: Duration, command: &'static str) -> Result<M, EngineError> {
+		match self.messages.recv_timeout(timeout) {
+			Ok(message) => Ok(message),
+			Err(RecvTimeoutError::Timeout) => Err(EngineError::Timeout(command)),
+			Err(RecvTimeoutError::Disconnected) => Err(EngineError::Disconnected)
+		}
+	}
+}
+
+impl<M> Drop for EngineProcess<M> {
+	// Both protocols quit with `quit`. Kill the engine if it doesn't do so soon
+	fn drop(&mut self) {
+		let _ = writeln!(self.writer, "quit").and_then(|_| self.writer.flush());
+
+		if let Some(child) = &mut self.child {
+			let start = Instant::now();
+			while start.elapsed() < Duration::from_secs(1) {
+				if let Ok(Some(_)) = child.try_wait() {
+					return;
+				}
+				thread::sleep(Duration::from_millis(10));
+			}
+
+			let _ = child.kill();
+			let _ = child.wait();
+		}
+	}
+}
+
+// Stand-ins for an engine process in tests: a script of commands and the lines answering them
+#[cfg(test)]
+pub(crate) mod fake {
+	use super::*;
+
+	struct FakeInput {
+		script: Vec<(&'static str, Vec<&'static str>)>,
+		output: mpsc::Sender<String>,
+		line: Vec<u8>,
+		received: mpsc::Sender<String>
+	}
+
+	// Answers each command named in the script with the lines that go with it
+	impl Write for FakeInput {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			for byte in buf {
+				if *byte != b'\n' {
+					self.line.push(*byte);
+					continue;
+				}
+
+				let command = String::from_utf8(std::mem::take(&mut self.line)).unwrap();
+				let name = command.split_whitespace().next();
+				if let Some((_, answer)) = self.script.iter().find(|(script_name, _)| name == Some(script_name)) {
+					for line in answer {
+						let _ = self.output.send(format!("{}\n", line));
+					}
+				}
+				let _ = self.received.send(command);
+			}
+
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	// The fake engine's output, ending once the engine is dropped
+	struct FakeOutput {
+		lines: Receiver<String>,
+		pending: Vec<u8>
+	}
+
+	impl Read for FakeOutput {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			if self.pending.is_empty() {
+				match self.lines.recv() {
+					Ok(line) => self.pending = line.into_bytes(),
+					Err(_) => return Ok(0)
+				}
+			}
+
+			let len = buf.len().min(self.pending.len());
+			buf[..len].copy_from_slice(&self.pending[..len]);
+			self.pending.drain(..len);
+			Ok(len)
+		}
+	}
+
+	// The fake engine's (output, input), and every command sent to it
+	pub(crate) fn fake_engine(script: Vec<(&'static str, Vec<&'static str>)>) -> (impl Read + Send + 'static, impl Write + Send + 'static, Receiver<String>) {
+		let (output, lines) = mpsc::channel();
+		let (received, commands) = mpsc::channel();
+		let input = FakeInput {
+			script,
+			output,
+			line: Vec::new(),
+			received
+		};
+		let output = FakeOutput {
+			lines,
+			pending: Vec::new()
+		};
+
+		(output, input, commands)
+	}
+}
@@ -0,0 +1,286 @@
+use std::{error::Error, fmt};
+
+use crate::{Move, PieceType, Position, Square};
+
+impl Position {
+	/// Standard Algebraic Notation for a legal move, e.g. `Nbd7`, `exd5`, `O-O` or `e8=Q#`.
+	pub fn san(&self, mv: Move) -> String {
+		let piece = self.piece_at(mv.from).expect("no piece to move");
+		let mut san = String::new();
+
+		if let Some((rook_from, _)) = self.castling_rook_move(mv) {
+			san.push_str(if rook_from.file() == 7 { "O-O" } else { "O-O-O" });
+		} else {
+			if piece.piece_type == PieceType::Pawn {
+				// Pawn captures name the file they come from
+				if self.is_capture(mv) {
+					san.push((b'a' + mv.from.file()) as char);
+				}
+			} else {
+				san.push(piece.piece_type.to_char());
+				san.push_str(&self.disambiguation(mv));
+			}
+
+			if self.is_capture(mv) {
+				san.push('x');
+			}
+			san.push_str(&mv.to.to_string());
+
+			if let Some(promotion) = mv.promotion {
+				san.push('=');
+				san.push(promotion.to_char());
+			}
+		}
+
+		let after = self.play(mv);
+		if after.is_in_check() {
+			san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+		}
+
+		san
+	}
+
+	/// SAN for each move of a line played from this position.
+	pub fn san_line(&self, moves: &[Move]) -> Vec<String> {
+		let mut position = self.clone();
+
+		moves
+			.iter()
+			.map(|mv| {
+				let san = position.san(*mv);
+				position.make_move(*mv);
+				san
+			})
+			.collect()
+	}
+
+	// The file, rank or both of the moving piece when another piece of the same type could also go there
+	fn disambiguation(&self, mv: Move) -> String {
+		let piece_type = self.piece_at(mv.from).map(|piece| piece.piece_type);
+		let others = self
+			.legal_moves()
+			.into_iter()
+			.filter(|other| other.to == mv.to && other.from != mv.from)
+			.filter(|other| self.piece_at(other.from).map(|piece| piece.piece_type) == piece_type)
+			.collect::<Vec<Move>>();
+
+		if others.is_empty() {
+			String::new()
+		} else if others.iter().all(|other| other.from.file() != mv.from.file()) {
+			((b'a' + mv.from.file()) as char).to_string()
+		} else if others.iter().all(|other| other.from.rank() != mv.from.rank()) {
+			((b'1' + mv.from.rank()) as char).to_string()
+		} else {
+			mv.from.to_string()
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SanError {
+	/// Not something SAN can describe.
+	Invalid(String),
+	/// Well formed, but no legal move matches it.
+	Illegal(String),
+	/// More than one legal move matches it.
+	Ambiguous(String)
+}
+
+impl fmt::Display for SanError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SanError::Invalid(san) => write!(f, "invalid move '{}'", san),
+			SanError::Illegal(san) => write!(f, "illegal move '{}'", san),
+			SanError::Ambiguous(san) => write!(f, "ambiguous move '{}'", san)
+		}
+	}
+}
+
+impl Error for SanError {}
+
+impl Position {
+	/// Finds the legal move a SAN string describes. Check marks, annotations like `!?` and a
+	/// trailing `e.p.` are ignored, and the `=` before a promotion and the `x` of a capture are optional.
+	pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+		let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+		let trimmed = trimmed.strip_suffix("e.p.").unwrap_or(trimmed);
+		let legal_moves = self.legal_moves();
+
+		let castling = match trimmed {
+			"O-O" | "0-0" => Some(true),
+			"O-O-O" | "0-0-0" => Some(false),
+			_ => None
+		};
+		if let Some(king_side) = castling {
+			return legal_moves
+				.into_iter()
+				.find(|mv| matches!(self.castling_rook_move(*mv), Some((rook_from, _)) if (rook_from.file() == 7) == king_side))
+				.ok_or_else(|| SanError::Illegal(san.to_string()));
+		}
+
+		let mut chars = trimmed.chars().filter(|c| *c != 'x' && *c != '-').collect::<Vec<char>>();
+
+		// A promotion is a piece letter after the target square. Lowercase letters are
+		// only taken after `=` so they can't be mistaken for a file
+		let mut promotion = None;
+		if let Some(last) = chars.last().copied() {
+			let after_equals = chars.len() > 1 && chars[chars.len() - 2] == '=';
+			if chars.len() > 2 && (last.is_ascii_uppercase() || after_equals) {
+				promotion = match PieceType::from_char(last) {
+					Some(PieceType::King) | Some(PieceType::Pawn) | None => return Err(SanError::Invalid(san.to_string())),
+					piece_type => piece_type
+				};
+				chars.pop();
+				if after_equals {
+					chars.pop();
+				}
+			}
+		}
+
+		// Pieces other than pawns start with their letter
+		let piece_type = match chars.first() {
+			Some(c) if c.is_ascii_uppercase() => {
+				let piece_type = PieceType::from_char(*c).ok_or_else(|| SanError::Invalid(san.to_string()))?;
+				chars.remove(0);
+				piece_type
+			},
+			_ => PieceType::Pawn
+		};
+
+		if chars.len() < 2 || chars.len() > 4 {
+			return Err(SanError::Invalid(san.to_string()));
+		}
+
+		let to_name = chars[chars.len() - 2..].iter().collect::<String>();
+		let to = Square::from_name(&to_name).ok_or_else(|| SanError::Invalid(san.to_string()))?;
+
+		// Whatever is left tells apart pieces that could both go there
+		let mut from_file = None;
+		let mut from_rank = None;
+		for c in &chars[..chars.len() - 2] {
+			match c {
+				'a'..='h' => from_file = Some(*c as u8 - b'a'),
+				'1'..='8' => from_rank = Some(*c as u8 - b'1'),
+				_ => return Err(SanError::Invalid(san.to_string()))
+			}
+		}
+
+		let candidates = legal_moves
+			.into_iter()
+			.filter(|mv| mv.to == to && mv.promotion == promotion)
+			.filter(|mv| matches!(self.piece_at(mv.from), Some(piece) if piece.piece_type == piece_type))
+			.filter(|mv| from_file.is_none_or(|file| mv.from.file() == file))
+			.filter(|mv| from_rank.is_none_or(|rank| mv.from.rank() == rank))
+			.collect::<Vec<Move>>();
+
+		match candidates.as_slice() {
+			[mv] => Ok(*mv),
+			[] => Err(SanError::Illegal(san.to_string())),
+			_ => Err(SanError::Ambiguous(san.to_string()))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	// Writes `uci` in `fen` as SAN, checking it reads back as the same move
+	fn san(fen: &str, uci: &str) -> String {
+		let position = Position::from_fen(fen).unwrap();
+		let mv = Move::from_uci(uci).unwrap();
+		let san = position.san(mv);
+		assert_eq!(position.parse_san(&san), Ok(mv), "{}", san);
+
+		san
+	}
+
+	#[test]
+	fn disambiguates_moves() {
+		let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+		assert_eq!(san(knights, "b1d2"), "Nbd2");
+		assert_eq!(san(knights, "f1d2"), "Nfd2");
+		assert_eq!(san(knights, "b1c3"), "Nc3");
+
+		let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+		assert_eq!(san(rooks, "a1a3"), "R1a3");
+		assert_eq!(san(rooks, "a5a3"), "R5a3");
+
+		let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+		assert_eq!(san(queens, "a1b2"), "Qa1b2");
+		assert_eq!(san(queens, "a3b2"), "Q3b2");
+		assert_eq!(san(queens, "c1b2"), "Qcb2");
+
+		let position = Position::from_fen(knights).unwrap();
+		assert_eq!(position.parse_san("Nd2"), Err(SanError::Ambiguous("Nd2".to_string())));
+		assert_eq!(position.parse_san("N1d2"), Err(SanError::Ambiguous("N1d2".to_string())));
+		assert_eq!(position.parse_san("Nb1d2"), Ok(Move::from_uci("b1d2").unwrap()));
+	}
+
+	#[test]
+	fn writes_pawn_moves_and_promotions() {
+		let fen = "k7/4P3/8/8/8/8/8/4K3 w - - 0 1";
+		assert_eq!(san(fen, "e7e8q"), "e8=Q+");
+		assert_eq!(san(fen, "e7e8n"), "e8=N");
+
+		let position = Position::from_fen(fen).unwrap();
+		for text in ["e8=Q", "e8Q", "e8=q", "e8Q+"] {
+			assert_eq!(position.parse_san(text), Ok(Move::from_uci("e7e8q").unwrap()), "{}", text);
+		}
+		assert_eq!(position.parse_san("e8"), Err(SanError::Illegal("e8".to_string())));
+		assert_eq!(position.parse_san("e8=K"), Err(SanError::Invalid("e8=K".to_string())));
+
+		let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2";
+		assert_eq!(san(en_passant, "e5d6"), "exd6");
+		let position = Position::from_fen(en_passant).unwrap();
+		assert_eq!(position.parse_san("exd6e.p."), Ok(Move::from_uci("e5d6").unwrap()));
+	}
+
+	#[test]
+	fn writes_castling() {
+		let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+		assert_eq!(san(fen, "e1g1"), "O-O");
+		assert_eq!(san(fen, "e1c1"), "O-O-O");
+		assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"), "O-O-O");
+
+		let position = Position::from_fen(fen).unwrap();
+		assert_eq!(position.parse_san("0-0"), Ok(Move::from_uci("e1g1").unwrap()));
+		assert_eq!(position.parse_san("0-0-0"), Ok(Move::from_uci("e1c1").unwrap()));
+
+		let no_rights = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+		assert_eq!(no_rights.parse_san("O-O"), Err(SanError::Illegal("O-O".to_string())));
+	}
+
+	#[test]
+	fn marks_checks_and_mates() {
+		assert_eq!(san("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7"), "Qxf7#");
+		assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8+");
+		assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a7"), "Ra7");
+
+		let position = Position::start();
+		assert!(matches!(position.parse_san("Ke2"), Err(SanError::Illegal(_))));
+		assert!(matches!(position.parse_san("Zz9"), Err(SanError::Invalid(_))));
+		assert_eq!(position.parse_san("Nf3!?"), Ok(Move::from_uci("g1f3").unwrap()));
+	}
+
+	#[test]
+	fn round_trips_every_move() {
+		for fen in [
+			START_FEN,
+			"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+			"8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+			"r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+			"rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+			"r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10"
+		] {
+			let position = Position::from_fen(fen).unwrap();
+			for mv in position.legal_moves() {
+				let after = position.play(mv);
+				for reply in after.legal_moves() {
+					assert_eq!(after.parse_san(&after.san(reply)), Ok(reply), "{} after {}", reply, mv);
+				}
+				assert_eq!(position.parse_san(&position.san(mv)), Ok(mv), "{}", mv);
+			}
+		}
+	}
+}
@@ -0,0 +1,316 @@
+use std::{
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant}
+};
+
+use crate::{evaluate, piece_value, Move, PieceType, Position};
+
+/// Score of being checkmated right now. Mates further away score closer to zero.
+pub const MATE_SCORE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_DEPTH: u32 = 64;
+
+/// When to stop searching. Without any limit the search runs until it's stopped.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SearchLimits {
+	pub depth: Option<u32>,
+	pub time: Option<Duration>,
+	pub nodes: Option<u64>
+}
+
+impl SearchLimits {
+	pub fn depth(depth: u32) -> Self {
+		SearchLimits {
+			depth: Some(depth),
+			..Default::default()
+		}
+	}
+
+	pub fn time(time: Duration) -> Self {
+		SearchLimits {
+			time: Some(time),
+			..Default::default()
+		}
+	}
+
+	/// A share of what's left on the clock plus most of the increment, keeping a little back
+	/// for the time it takes to send the move. Without `moves_to_go` the game is assumed to
+	/// last another 30 moves.
+	pub fn clock(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+		let moves_left = moves_to_go.unwrap_or(30).max(1);
+		let share = remaining / moves_left + increment * 3 / 4;
+		let most = remaining.saturating_sub(Duration::from_millis(50));
+
+		SearchLimits::time(share.min(most).max(Duration::from_millis(1)))
+	}
+}
+
+/// Progress after each completed iteration of the search.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+	pub depth: u32,
+	/// Centipawns from the side to move's point of view, see [`mate_in`] for mate scores.
+	pub score: i32,
+	pub nodes: u64,
+	pub time: Duration,
+	/// The best line found, starting with the best move.
+	pub pv: Vec<Move>
+}
+
+/// Moves until mate (negative when getting mated) if the score is a mate score.
+pub fn mate_in(score: i32) -> Option<i32> {
+	if score.abs() < MATE_SCORE - MAX_DEPTH as i32 {
+		return None;
+	}
+
+	let plies = MATE_SCORE - score.abs();
+	let moves = (plies + 1) / 2;
+	Some(if score > 0 { moves } else { -moves })
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+	/// None when there are no legal moves.
+	pub best_move: Option<Move>,
+	pub score: i32,
+	pub depth: u32,
+	pub nodes: u64
+}
+
+struct Searcher<'a> {
+	limits: SearchLimits,
+	stop: &'a AtomicBool,
+	start: Instant,
+	nodes: u64,
+	// Plies from the root, so quicker mates score higher
+	ply: i32,
+	aborted: bool
+}
+
+/// Iterative deepening negamax with alpha-beta pruning and a quiescence search on captures.
+/// `on_info` is called after every completed depth. Setting `stop` ends the search early,
+/// returning the best move of the last completed depth.
+pub fn search(position: &Position, limits: SearchLimits, stop: &AtomicBool, mut on_info: impl FnMut(&SearchInfo)) -> SearchResult {
+	let mut searcher = Searcher {
+		limits,
+		stop,
+		start: Instant::now(),
+		nodes: 0,
+		ply: 0,
+		aborted: false
+	};
+
+	let legal_moves = position.legal_moves();
+	let mut result = SearchResult {
+		best_move: legal_moves.first().copied(),
+		score: 0,
+		depth: 0,
+		nodes: 0
+	};
+
+	// Nothing to think about with a single move
+	if legal_moves.len() <= 1 {
+		return result;
+	}
+
+	let mut position = position.clone();
+	let mut pv = Vec::new();
+	let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+
+	for depth in 1..=max_depth {
+		let mut line = Vec::new();
+		let score = searcher.negamax(&mut position, depth, -INFINITY, INFINITY, &pv, &mut line);
+
+		if searcher.aborted {
+			break;
+		}
+
+		pv = line;
+		result = SearchResult {
+			best_move: pv.first().copied().or(result.best_move),
+			score,
+			depth,
+			nodes: searcher.nodes
+		};
+
+		on_info(&SearchInfo {
+			depth,
+			score,
+			nodes: searcher.nodes,
+			time: searcher.start.elapsed(),
+			pv: pv.clone()
+		});
+
+		// No point looking deeper once a mate is found
+		if mate_in(score).is_some() {
+			break;
+		}
+	}
+
+	result.nodes = searcher.nodes;
+	result
+}
+
+impl Searcher<'_> {
+	fn should_stop(&mut self) -> bool {
+		if self.aborted {
+			return true;
+		}
+
+		if self.nodes & 1023 == 0 {
+			let out_of_time = matches!(self.limits.time, Some(time) if self.start.elapsed() >= time);
+			let out_of_nodes = matches!(self.limits.nodes, Some(nodes) if self.nodes >= nodes);
+			self.aborted = out_of_time || out_of_nodes || self.stop.load(Ordering::Relaxed);
+		}
+
+		self.aborted
+	}
+
+	// `previous_pv` is the best line from the last iteration, tried first while we're still on it
+	fn negamax(&mut self, position: &mut Position, depth: u32, mut alpha: i32, beta: i32, previous_pv: &[Move], pv: &mut Vec<Move>) -> i32 {
+		self.nodes += 1;
+		if self.should_stop() {
+			return 0;
+		}
+
+		let mut moves = position.legal_moves();
+		if moves.is_empty() {
+			return if position.is_in_check() { -MATE_SCORE + self.ply } else { 0 };
+		}
+
+		if depth == 0 {
+			return self.quiescence(position, alpha, beta);
+		}
+
+		order_moves(position, &mut moves, previous_pv.first().copied());
+
+		for mv in moves {
+			let following_pv = if previous_pv.first() == Some(&mv) { &previous_pv[1..] } else { &[] };
+			let mut line = Vec::new();
+
+			let undo = position.make_move(mv);
+			self.ply += 1;
+			let score = -self.negamax(position, depth - 1, -beta, -alpha, following_pv, &mut line);
+			self.ply -= 1;
+			position.unmake_move(mv, undo);
+
+			if self.aborted {
+				return 0;
+			}
+
+			if score >= beta {
+				return beta;
+			}
+
+			if score > alpha {
+				alpha = score;
+				pv.clear();
+				pv.push(mv);
+				pv.extend(line);
+			}
+		}
+
+		alpha
+	}
+
+	// Only look at captures and promotions so the evaluation isn't taken in the middle of an exchange
+	fn quiescence(&mut self, position: &mut Position, mut alpha: i32, beta: i32) -> i32 {
+		self.nodes += 1;
+		if self.should_stop() {
+			return 0;
+		}
+
+		let stand_pat = evaluate(position);
+		if stand_pat >= beta {
+			return beta;
+		}
+		alpha = alpha.max(stand_pat);
+
+		let mut moves = position
+			.legal_moves()
+			.into_iter()
+			.filter(|mv| position.is_capture(*mv) || mv.promotion == Some(PieceType::Queen))
+			.collect::<Vec<Move>>();
+		order_moves(position, &mut moves, None);
+
+		for mv in moves {
+			let undo = position.make_move(mv);
+			self.ply += 1;
+			let score = -self.quiescence(position, -beta, -alpha);
+			self.ply -= 1;
+			position.unmake_move(mv, undo);
+
+			if self.aborted {
+				return 0;
+			}
+
+			if score >= beta {
+				return beta;
+			}
+			alpha = alpha.max(score);
+		}
+
+		alpha
+	}
+}
+
+// Best move of the previous iteration first, then captures of valuable pieces by cheap ones, then promotions
+fn order_moves(position: &Position, moves: &mut [Move], best_move: Option<Move>) {
+	moves.sort_by_cached_key(|mv| {
+		if Some(*mv) == best_move {
+			return i32::MIN;
+		}
+
+		let mut score = 0;
+		if let Some(square) = position.captured_square(*mv) {
+			let victim = position.piece_at(square).map_or(0, |piece| piece_value(piece.piece_type));
+			let attacker = position.piece_at(mv.from).map_or(0, |piece| piece_value(piece.piece_type));
+			score -= 10 * victim - attacker + 10_000;
+		}
+		if let Some(promotion) = mv.promotion {
+			score -= piece_value(promotion);
+		}
+
+		score
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+	use std::sync::atomic::AtomicBool;
+
+	fn best_move(fen: &str, depth: u32) -> SearchResult {
+		let position = Position::from_fen(fen).unwrap();
+		search(&position, SearchLimits::depth(depth), &AtomicBool::new(false), |_| {})
+	}
+
+	#[test]
+	fn finds_mate_in_one() {
+		let result = best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+		assert_eq!(result.best_move, Move::from_uci("a1a8"));
+		assert_eq!(mate_in(result.score), Some(1));
+
+		let result = best_move("6rk/6pp/8/6N1/8/8/8/6K1 w - - 0 1", 3);
+		assert_eq!(result.best_move, Move::from_uci("g5f7"));
+		assert_eq!(mate_in(result.score), Some(1));
+	}
+
+	#[test]
+	fn takes_the_free_piece() {
+		assert_eq!(best_move("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 3).best_move, Move::from_uci("d1d5"));
+		assert_eq!(best_move("r3k3/8/8/8/8/8/8/N3K3 b - - 0 1", 3).best_move, Move::from_uci("a8a1"));
+	}
+
+	#[test]
+	fn stops_when_asked() {
+		let position = Position::start();
+		// Without limits only the stop flag ends the search, checked every so many nodes
+		let result = search(&position, SearchLimits::default(), &AtomicBool::new(true), |_| {});
+		assert!(result.depth < 4);
+		assert!(result.best_move.is_some_and(|mv| position.is_legal(mv)));
+
+		let mated = Position::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+		assert_eq!(search(&mated, SearchLimits::depth(3), &AtomicBool::new(false), |_| {}).best_move, None);
+	}
+}
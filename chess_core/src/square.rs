@@ -0,0 +1,68 @@
+use std::fmt;
+
+/// One of the 64 squares, stored as `rank * 8 + file` (a1 is 0, h1 is 7 and h8 is 63).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Square(u8);
+
+impl Square {
+	pub fn new(rank: u8, file: u8) -> Square {
+		debug_assert!(rank < 8 && file < 8, "square out of the board");
+		Square(rank * 8 + file)
+	}
+
+	pub fn from_index(index: usize) -> Square {
+		debug_assert!(index < 64, "square out of the board");
+		Square(index as u8)
+	}
+
+	pub fn index(&self) -> usize {
+		self.0 as usize
+	}
+
+	pub fn rank(&self) -> u8 {
+		self.0 / 8
+	}
+
+	pub fn file(&self) -> u8 {
+		self.0 % 8
+	}
+
+	/// The square `rank_delta` ranks and `file_delta` files away, if it's still on the board.
+	pub fn offset(&self, rank_delta: i8, file_delta: i8) -> Option<Square> {
+		let rank = self.rank() as i8 + rank_delta;
+		let file = self.file() as i8 + file_delta;
+
+		if (0..8).contains(&rank) && (0..8).contains(&file) {
+			Some(Square::new(rank as u8, file as u8))
+		} else {
+			None
+		}
+	}
+
+	pub fn all() -> impl Iterator<Item = Square> {
+		(0..64).map(Square)
+	}
+
+	/// Parses a square name such as `e4`.
+	pub fn from_name(name: &str) -> Option<Square> {
+		let bytes = name.as_bytes();
+		if bytes.len() != 2 {
+			return None;
+		}
+
+		let file = bytes[0].wrapping_sub(b'a');
+		let rank = bytes[1].wrapping_sub(b'1');
+
+		if rank < 8 && file < 8 {
+			Some(Square::new(rank, file))
+		} else {
+			None
+		}
+	}
+}
+
+impl fmt::Display for Square {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}{}", (b'a' + self.file()) as char, (b'1' + self.rank()) as char)
+	}
+}
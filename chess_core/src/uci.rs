@@ -0,0 +1,580 @@
+use std::{
+	fmt,
+	io::{Read, Write},
+	time::Duration
+};
+
+use crate::{mate_in, process::EngineProcess, EngineError, Move, PieceColor, Position, SearchInfo, SearchLimits, START_FEN};
+
+/// An engine's evaluation, from the side to move's point of view.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UciScore {
+	Centipawns(i32),
+	/// Moves until mate, negative when the engine is getting mated.
+	Mate(i32)
+}
+
+/// What an engine reports while searching, read from an `info` line. Fields it didn't send are left empty.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct UciInfo {
+	pub depth: Option<u32>,
+	pub seldepth: Option<u32>,
+	pub multipv: Option<u32>,
+	pub score: Option<UciScore>,
+	pub nodes: Option<u64>,
+	pub nps: Option<u64>,
+	/// Milliseconds spent searching.
+	pub time: Option<u64>,
+	pub pv: Vec<Move>,
+	pub string: Option<String>
+}
+
+impl UciInfo {
+	/// Reads the fields after `info`, skipping the ones it doesn't know.
+	pub fn parse(fields: &str) -> UciInfo {
+		let mut info = UciInfo::default();
+		let mut tokens = fields.split_whitespace().peekable();
+
+		while let Some(token) = tokens.next() {
+			match token {
+				"depth" => info.depth = tokens.next().and_then(|value| value.parse().ok()),
+				"seldepth" => info.seldepth = tokens.next().and_then(|value| value.parse().ok()),
+				"multipv" => info.multipv = tokens.next().and_then(|value| value.parse().ok()),
+				"nodes" => info.nodes = tokens.next().and_then(|value| value.parse().ok()),
+				"nps" => info.nps = tokens.next().and_then(|value| value.parse().ok()),
+				"time" => info.time = tokens.next().and_then(|value| value.parse().ok()),
+				"score" => {
+					let kind = tokens.next();
+					let value = tokens.next().and_then(|value| value.parse().ok());
+					info.score = match (kind, value) {
+						(Some("cp"), Some(value)) => Some(UciScore::Centipawns(value)),
+						(Some("mate"), Some(value)) => Some(UciScore::Mate(value)),
+						_ => None
+					};
+
+					// Bounds from an aspiration window are read as the score itself
+					while matches!(tokens.peek(), Some(&"lowerbound") | Some(&"upperbound")) {
+						tokens.next();
+					}
+				},
+				"pv" => {
+					while let Some(mv) = tokens.peek().and_then(|mv| Move::from_uci(mv)) {
+						info.pv.push(mv);
+						tokens.next();
+					}
+				},
+				"string" => {
+					info.string = Some(tokens.by_ref().collect::<Vec<&str>>().join(" "));
+				},
+				_ => {}
+			}
+		}
+
+		info
+	}
+}
+
+/// The fields after `info`, in the order engines usually send them.
+impl fmt::Display for UciInfo {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut fields = Vec::new();
+		let numbers = [
+			("depth", self.depth.map(u64::from)),
+			("seldepth", self.seldepth.map(u64::from)),
+			("multipv", self.multipv.map(u64::from))
+		];
+		for (name, value) in numbers {
+			if let Some(value) = value {
+				fields.push(format!("{} {}", name, value));
+			}
+		}
+
+		match self.score {
+			Some(UciScore::Centipawns(centipawns)) => fields.push(format!("score cp {}", centipawns)),
+			Some(UciScore::Mate(moves)) => fields.push(format!("score mate {}", moves)),
+			None => {}
+		}
+
+		for (name, value) in [("nodes", self.nodes), ("nps", self.nps), ("time", self.time)] {
+			if let Some(value) = value {
+				fields.push(format!("{} {}", name, value));
+			}
+		}
+
+		if !self.pv.is_empty() {
+			fields.push(format!("pv {}", self.pv.iter().map(|mv| mv.to_string()).collect::<Vec<String>>().join(" ")));
+		}
+
+		// Everything after `string` is part of it, so it goes last
+		if let Some(string) = &self.string {
+			fields.push(format!("string {}", string));
+		}
+
+		write!(f, "{}", fields.join(" "))
+	}
+}
+
+impl From<&SearchInfo> for UciInfo {
+	fn from(info: &SearchInfo) -> Self {
+		let millis = info.time.as_millis() as u64;
+
+		UciInfo {
+			depth: Some(info.depth),
+			score: Some(match mate_in(info.score) {
+				Some(moves) => UciScore::Mate(moves),
+				None => UciScore::Centipawns(info.score)
+			}),
+			nodes: Some(info.nodes),
+			nps: Some(info.nodes * 1000 / millis.max(1)),
+			time: Some(millis),
+			pv: info.pv.clone(),
+			..Default::default()
+		}
+	}
+}
+
+/// A line sent by a UCI engine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciMessage {
+	IdName(String),
+	IdAuthor(String),
+	UciOk,
+	ReadyOk,
+	/// The end of a search. `best_move` is None if there were no legal moves.
+	BestMove {
+		best_move: Option<Move>,
+		ponder: Option<Move>
+	},
+	Info(UciInfo),
+	/// Anything else, like `option` lines, which aren't used.
+	Other(String)
+}
+
+impl UciMessage {
+	pub fn parse(line: &str) -> UciMessage {
+		let line = line.trim();
+		let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+		let rest = rest.trim();
+
+		match command {
+			"uciok" => UciMessage::UciOk,
+			"readyok" => UciMessage::ReadyOk,
+			"id" => match rest.split_once(char::is_whitespace) {
+				Some(("name", name)) => UciMessage::IdName(name.trim().to_string()),
+				Some(("author", author)) => UciMessage::IdAuthor(author.trim().to_string()),
+				_ => UciMessage::Other(line.to_string())
+			},
+			"bestmove" => {
+				let mut tokens = rest.split_whitespace();
+				let best_move = tokens.next().and_then(Move::from_uci);
+				let ponder = match (tokens.next(), tokens.next()) {
+					(Some("ponder"), Some(mv)) => Move::from_uci(mv),
+					_ => None
+				};
+
+				UciMessage::BestMove { best_move, ponder }
+			},
+			"info" => UciMessage::Info(UciInfo::parse(rest)),
+			_ => UciMessage::Other(line.to_string())
+		}
+	}
+}
+
+/// A chess engine speaking the Universal Chess Interface, usually running as a child process.
+///
+/// Its output is read on a separate thread, so messages can be polled without blocking with
+/// [`UciEngine::try_recv`].
+pub struct UciEngine {
+	process: EngineProcess<UciMessage>,
+	name: Option<String>
+}
+
+impl UciEngine {
+	/// Starts the engine binary at `path`. It still needs to be set up with [`UciEngine::init`].
+	pub fn spawn(path: &str) -> Result<UciEngine, EngineError> {
+		Ok(UciEngine {
+			process: EngineProcess::spawn(path, UciMessage::parse)?,
+			name: None
+		})
+	}
+
+	/// An engine talking over any pair of streams, `reader` being its output.
+	pub fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> UciEngine {
+		UciEngine {
+			process: EngineProcess::new(reader, writer, UciMessage::parse),
+			name: None
+		}
+	}
+
+	/// The name the engine gave itself during [`UciEngine::init`].
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	/// Sends a command, one line.
+	pub fn send(&mut self, command: &str) -> Result<(), EngineError> {
+		self.process.send(command)
+	}
+
+	/// Switches the engine to UCI and waits until it's ready, waiting at most `timeout` for each answer.
+	pub fn init(&mut self, timeout: Duration) -> Result<(), EngineError> {
+		self.send("uci")?;
+		loop {
+			match self.process.recv_timeout(timeout, "uci")? {
+				UciMessage::IdName(name) => self.name = Some(name),
+				UciMessage::UciOk => break,
+				_ => {}
+			}
+		}
+
+		self.send("ucinewgame")?;
+		self.wait_ready(timeout)
+	}
+
+	/// Waits for the engine to finish whatever it's doing.
+	pub fn wait_ready(&mut self, timeout: Duration) -> Result<(), EngineError> {
+		self.send("isready")?;
+		while self.process.recv_timeout(timeout, "isready")? != UciMessage::ReadyOk {}
+
+		Ok(())
+	}
+
+	/// Sets the position to search as the moves played from `start`, which lets the engine see repetitions.
+	pub fn set_position(&mut self, start: &Position, moves: &[Move]) -> Result<(), EngineError> {
+		let fen = start.to_fen();
+		let mut command = if fen == START_FEN {
+			"position startpos".to_string()
+		} else {
+			format!("position fen {}", fen)
+		};
+
+		if !moves.is_empty() {
+			command.push_str(" moves");
+			for mv in moves {
+				command.push_str(&format!(" {}", mv));
+			}
+		}
+
+		self.send(&command)
+	}
+
+	/// Starts searching the current position. Without any limits the engine searches until [`UciEngine::stop`].
+	pub fn go(&mut self, limits: &SearchLimits) -> Result<(), EngineError> {
+		let mut command = "go".to_string();
+		if let Some(depth) = limits.depth {
+			command.push_str(&format!(" depth {}", depth));
+		}
+		if let Some(nodes) = limits.nodes {
+			command.push_str(&format!(" nodes {}", nodes));
+		}
+		if let Some(time) = limits.time {
+			command.push_str(&format!(" movetime {}", time.as_millis()));
+		}
+		if limits.depth.is_none() && limits.nodes.is_none() && limits.time.is_none() {
+			command.push_str(" infinite");
+		}
+
+		self.send(&command)
+	}
+
+	/// Ends the search early, the engine still answers with `bestmove`.
+	pub fn stop(&mut self) -> Result<(), EngineError> {
+		self.send("stop")
+	}
+
+	/// The next message if there's one waiting.
+	pub fn try_recv(&self) -> Result<Option<UciMessage>, EngineError> {
+		self.process.try_recv()
+	}
+
+	/// Waits for the next message.
+	pub fn recv_timeout(&self, timeout: Duration) -> Result<UciMessage, EngineError> {
+		self.process.recv_timeout(timeout, "the last command")
+	}
+}
+
+/// Limits of a `go` command, with times in milliseconds.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct UciGo {
+	pub wtime: Option<u64>,
+	pub btime: Option<u64>,
+	pub winc: Option<u64>,
+	pub binc: Option<u64>,
+	pub movestogo: Option<u32>,
+	pub depth: Option<u32>,
+	pub nodes: Option<u64>,
+	pub movetime: Option<u64>,
+	/// Search until told to stop.
+	pub infinite: bool
+}
+
+impl UciGo {
+	/// What the built-in search gets with `color` to move: all of `movetime`, or its share of the clock.
+	pub fn limits(&self, color: PieceColor) -> SearchLimits {
+		let (remaining, increment) = match color {
+			PieceColor::White => (self.wtime, self.winc),
+			PieceColor::Black => (self.btime, self.binc)
+		};
+
+		let mut limits = match (self.infinite, self.movetime, remaining) {
+			(true, _, _) => SearchLimits::default(),
+			(false, Some(movetime), _) => SearchLimits::time(Duration::from_millis(movetime)),
+			(false, None, Some(remaining)) => SearchLimits::clock(
+				Duration::from_millis(remaining),
+				Duration::from_millis(increment.unwrap_or(0)),
+				self.movestogo
+			),
+			(false, None, None) => SearchLimits::default()
+		};
+		limits.depth = self.depth;
+		limits.nodes = self.nodes;
+
+		limits
+	}
+}
+
+/// A command sent to a UCI engine by the GUI.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciCommand {
+	Uci,
+	Debug(bool),
+	IsReady,
+	SetOption {
+		name: String,
+		value: Option<String>
+	},
+	UciNewGame,
+	/// The position to search, as moves played from `start`. The moves haven't been checked yet.
+	Position {
+		start: Position,
+		moves: Vec<Move>
+	},
+	Go(UciGo),
+	/// `go perft <depth>`, which isn't part of UCI but is what engines commonly take to count
+	/// the moves from the current position, split by the first move.
+	Perft(u32),
+	Stop,
+	PonderHit,
+	Quit
+}
+
+impl UciCommand {
+	pub fn parse(line: &str) -> Result<UciCommand, EngineError> {
+		let line = line.trim();
+		let mut tokens = line.split_whitespace();
+		let invalid = || EngineError::InvalidCommand(line.to_string());
+
+		match tokens.next() {
+			Some("uci") => Ok(UciCommand::Uci),
+			Some("debug") => Ok(UciCommand::Debug(tokens.next() != Some("off"))),
+			Some("isready") => Ok(UciCommand::IsReady),
+			Some("ucinewgame") => Ok(UciCommand::UciNewGame),
+			Some("stop") => Ok(UciCommand::Stop),
+			Some("ponderhit") => Ok(UciCommand::PonderHit),
+			Some("quit") => Ok(UciCommand::Quit),
+			Some("setoption") => {
+				let rest = tokens.collect::<Vec<&str>>().join(" ");
+				let rest = rest.strip_prefix("name ").ok_or_else(invalid)?;
+				let (name, value) = match rest.split_once(" value ") {
+					Some((name, value)) => (name, Some(value.to_string())),
+					None => (rest, None)
+				};
+
+				Ok(UciCommand::SetOption {
+					name: name.to_string(),
+					value
+				})
+			},
+			Some("position") => {
+				let rest = tokens.collect::<Vec<&str>>().join(" ");
+				let (setup, moves) = match rest.split_once("moves") {
+					Some((setup, moves)) => (setup.trim(), moves),
+					None => (rest.trim(), "")
+				};
+
+				let start = match setup.split_once(' ') {
+					_ if setup == "startpos" => Position::start(),
+					Some(("fen", fen)) => Position::from_fen(fen.trim()).map_err(EngineError::InvalidFen)?,
+					_ => return Err(invalid())
+				};
+				let moves = moves
+					.split_whitespace()
+					.map(|mv| Move::from_uci(mv).ok_or_else(invalid))
+					.collect::<Result<Vec<Move>, EngineError>>()?;
+
+				Ok(UciCommand::Position { start, moves })
+			},
+			Some("go") => {
+				let mut go = UciGo::default();
+				while let Some(token) = tokens.next() {
+					let mut value = || tokens.next().and_then(|value| value.parse::<u64>().ok());
+					match token {
+						"wtime" => go.wtime = value(),
+						"btime" => go.btime = value(),
+						"winc" => go.winc = value(),
+						"binc" => go.binc = value(),
+						"movestogo" => go.movestogo = value().map(|moves| moves as u32),
+						"depth" => go.depth = value().map(|depth| depth as u32),
+						"nodes" => go.nodes = value(),
+						"movetime" => go.movetime = value(),
+						"infinite" => go.infinite = true,
+						"perft" => return value().map(|depth| UciCommand::Perft(depth as u32)).ok_or_else(invalid),
+						// Pondering and searchmoves aren't supported, so their moves are skipped over
+						_ => {}
+					}
+				}
+
+				Ok(UciCommand::Go(go))
+			},
+			_ => Err(invalid())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc::Receiver;
+
+	use super::*;
+	use crate::process::fake;
+
+	fn fake_engine(script: Vec<(&'static str, Vec<&'static str>)>) -> (UciEngine, Receiver<String>) {
+		let (output, input, commands) = fake::fake_engine(script);
+		(UciEngine::new(output, input), commands)
+	}
+
+	const TIMEOUT: Duration = Duration::from_secs(5);
+
+	#[test]
+	fn plays_a_scripted_game() {
+		let (mut engine, commands) = fake_engine(vec![
+			("uci", vec!["id name Fake 1.0", "id author Nobody", "option name Hash type spin default 16 min 1 max 1024", "uciok"]),
+			("isready", vec!["readyok"]),
+			("go", vec![
+				"info depth 1 seldepth 2 score cp 13 nodes 20 nps 2000 time 10 pv e7e5",
+				"info depth 2 score mate -3 lowerbound nodes 80 pv e7e5 g1f3 string so long",
+				"bestmove e7e5 ponder g1f3"
+			])
+		]);
+
+		engine.init(TIMEOUT).unwrap();
+		assert_eq!(engine.name(), Some("Fake 1.0"));
+
+		let start = Position::start();
+		let e4 = Move::from_uci("e2e4").unwrap();
+		engine.set_position(&start, &[e4]).unwrap();
+		engine.go(&SearchLimits::time(Duration::from_millis(500))).unwrap();
+
+		let mut infos = Vec::new();
+		let best_move = loop {
+			match engine.recv_timeout(TIMEOUT).unwrap() {
+				UciMessage::Info(info) => infos.push(info),
+				UciMessage::BestMove { best_move, ponder } => {
+					assert_eq!(ponder, Move::from_uci("g1f3"));
+					break best_move;
+				},
+				message => panic!("unexpected {:?}", message)
+			}
+		};
+
+		assert_eq!(best_move, Move::from_uci("e7e5"));
+		assert!(start.play(e4).is_legal(best_move.unwrap()));
+
+		assert_eq!(infos.len(), 2);
+		assert_eq!(infos[0].depth, Some(1));
+		assert_eq!(infos[0].seldepth, Some(2));
+		assert_eq!(infos[0].score, Some(UciScore::Centipawns(13)));
+		assert_eq!(infos[0].nodes, Some(20));
+		assert_eq!(infos[0].time, Some(10));
+		assert_eq!(infos[0].pv, vec![Move::from_uci("e7e5").unwrap()]);
+		assert_eq!(infos[1].score, Some(UciScore::Mate(-3)));
+		assert_eq!(infos[1].pv.len(), 2);
+		assert_eq!(infos[1].string.as_deref(), Some("so long"));
+
+		let sent = commands.try_iter().collect::<Vec<String>>();
+		assert_eq!(sent, vec!["uci", "ucinewgame", "isready", "position startpos moves e2e4", "go movetime 500"]);
+	}
+
+	#[test]
+	fn sends_custom_positions_as_fen() {
+		let (mut engine, commands) = fake_engine(Vec::new());
+		let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+
+		engine.set_position(&Position::from_fen(fen).unwrap(), &[]).unwrap();
+		engine.go(&SearchLimits::default()).unwrap();
+		engine.stop().unwrap();
+
+		let sent = commands.try_iter().collect::<Vec<String>>();
+		assert_eq!(sent, vec![format!("position fen {}", fen), "go infinite".to_string(), "stop".to_string()]);
+	}
+
+	#[test]
+	fn times_out_on_a_silent_engine() {
+		let (mut engine, _commands) = fake_engine(Vec::new());
+		assert!(matches!(engine.init(Duration::from_millis(50)), Err(EngineError::Timeout("uci"))));
+	}
+
+	#[test]
+	fn parses_engine_lines() {
+		assert_eq!(UciMessage::parse("bestmove (none)"), UciMessage::BestMove { best_move: None, ponder: None });
+		assert_eq!(UciMessage::parse("bestmove a7a8n"), UciMessage::BestMove {
+			best_move: Move::from_uci("a7a8n"),
+			ponder: None
+		});
+		assert_eq!(UciMessage::parse("  readyok  "), UciMessage::ReadyOk);
+		assert_eq!(UciMessage::parse("copyprotection ok"), UciMessage::Other("copyprotection ok".to_string()));
+		assert_eq!(Move::from_uci("e7e8k"), None);
+		assert_eq!(Move::from_uci("e9e8"), None);
+	}
+
+	#[test]
+	fn parses_gui_commands() {
+		assert_eq!(UciCommand::parse("isready").unwrap(), UciCommand::IsReady);
+		assert_eq!(UciCommand::parse("setoption name Clear Hash").unwrap(), UciCommand::SetOption {
+			name: "Clear Hash".to_string(),
+			value: None
+		});
+		assert_eq!(UciCommand::parse("setoption name Hash value 64").unwrap(), UciCommand::SetOption {
+			name: "Hash".to_string(),
+			value: Some("64".to_string())
+		});
+
+		let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+		assert_eq!(UciCommand::parse(&format!("position fen {} moves e2e4 e8d7", fen)).unwrap(), UciCommand::Position {
+			start: Position::from_fen(fen).unwrap(),
+			moves: vec![Move::from_uci("e2e4").unwrap(), Move::from_uci("e8d7").unwrap()]
+		});
+		assert_eq!(UciCommand::parse("position startpos").unwrap(), UciCommand::Position {
+			start: Position::start(),
+			moves: Vec::new()
+		});
+		assert!(UciCommand::parse("position fen 8/8 w").is_err());
+		assert!(UciCommand::parse("position startpos moves e2e9").is_err());
+		assert!(UciCommand::parse("hello").is_err());
+
+		let go = match UciCommand::parse("go wtime 60000 btime 30000 winc 1000 binc 1000 movestogo 20").unwrap() {
+			UciCommand::Go(go) => go,
+			command => panic!("unexpected {:?}", command)
+		};
+		assert_eq!(go.limits(PieceColor::White).time, Some(Duration::from_millis(3750)));
+		assert_eq!(go.limits(PieceColor::Black).time, Some(Duration::from_millis(2250)));
+		assert_eq!(go.limits(PieceColor::White).depth, None);
+
+		let go = match UciCommand::parse("go infinite").unwrap() {
+			UciCommand::Go(go) => go,
+			command => panic!("unexpected {:?}", command)
+		};
+		assert!(go.infinite);
+		assert_eq!(go.limits(PieceColor::White).time, None);
+
+		assert_eq!(UciCommand::parse("go perft 4").unwrap(), UciCommand::Perft(4));
+		assert!(UciCommand::parse("go perft").is_err());
+	}
+
+	#[test]
+	fn writes_info_lines() {
+		let info = UciInfo::parse("depth 5 score mate -2 nodes 1000 time 20 pv e2e4 e7e5 string hi there");
+		assert_eq!(info.to_string(), "depth 5 score mate -2 nodes 1000 time 20 pv e2e4 e7e5 string hi there");
+		assert_eq!(UciInfo::parse(&info.to_string()), info);
+	}
+}
@@ -0,0 +1,573 @@
+use std::{
+	fmt,
+	io::{Read, Write},
+	time::Duration
+};
+
+use crate::{mate_in, process::EngineProcess, EngineError, Move, PgnResult, Position, SearchInfo, SearchLimits, START_FEN};
+
+/// Scores at or beyond this mean mate, as in `100000 + N` for mate in N moves.
+pub const XBOARD_MATE_SCORE: i32 = 100_000;
+
+/// A command sent to a CECP (XBoard/WinBoard) engine by the GUI.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum XBoardCommand {
+	XBoard,
+	Protover(u32),
+	Accepted(String),
+	Rejected(String),
+	New,
+	Force,
+	Go,
+	/// The opponent's move, sent with `usermove` or on its own. It's kept as text, since it
+	/// can be in SAN or coordinates, and is only read once it's clear which position it's for.
+	UserMove(String),
+	SetBoard(Position),
+	/// `moves` to make in `base` time, 0 for all of them, adding `increment` after each move.
+	Level {
+		moves: u32,
+		base: Duration,
+		increment: Duration
+	},
+	/// A fixed time for every move.
+	St(Duration),
+	/// A depth limit.
+	Sd(u32),
+	/// Time left on the engine's clock.
+	Time(Duration),
+	/// Time left on the opponent's clock.
+	Otim(Duration),
+	Result {
+		result: PgnResult,
+		comment: String
+	},
+	Ping(u32),
+	Undo,
+	Remove,
+	/// Move now, `?`.
+	MoveNow,
+	Post,
+	NoPost,
+	Quit,
+	/// Commands that don't change anything for this engine, like `hard` or `computer`.
+	Ignored(String)
+}
+
+impl XBoardCommand {
+	pub fn parse(line: &str) -> Result<XBoardCommand, EngineError> {
+		let line = line.trim();
+		let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+		let rest = rest.trim();
+		let invalid = || EngineError::InvalidCommand(line.to_string());
+		let number = || rest.parse::<u32>().map_err(|_| invalid());
+		let centiseconds = || rest.parse::<u64>().map(|centiseconds| Duration::from_millis(centiseconds * 10)).map_err(|_| invalid());
+
+		match command {
+			"xboard" => Ok(XBoardCommand::XBoard),
+			"protover" => Ok(XBoardCommand::Protover(number()?)),
+			"accepted" => Ok(XBoardCommand::Accepted(rest.to_string())),
+			"rejected" => Ok(XBoardCommand::Rejected(rest.to_string())),
+			"new" => Ok(XBoardCommand::New),
+			"force" => Ok(XBoardCommand::Force),
+			"go" => Ok(XBoardCommand::Go),
+			"usermove" if !rest.is_empty() => Ok(XBoardCommand::UserMove(rest.to_string())),
+			"setboard" => Position::from_fen(rest).map(XBoardCommand::SetBoard).map_err(EngineError::InvalidFen),
+			"level" => {
+				let fields = rest.split_whitespace().collect::<Vec<&str>>();
+				if fields.len() != 3 {
+					return Err(invalid());
+				}
+
+				// The base time is in minutes, optionally with seconds as in 0:30
+				let (minutes, seconds) = fields[1].split_once(':').unwrap_or((fields[1], "0"));
+				let base = minutes.parse::<u64>().ok().zip(seconds.parse::<u64>().ok()).ok_or_else(invalid)?;
+
+				Ok(XBoardCommand::Level {
+					moves: fields[0].parse().map_err(|_| invalid())?,
+					base: Duration::from_secs(base.0 * 60 + base.1),
+					increment: Duration::from_secs_f32(fields[2].parse::<f32>().map_err(|_| invalid())?.max(0.))
+				})
+			},
+			"st" => Ok(XBoardCommand::St(Duration::from_secs(number()? as u64))),
+			"sd" => Ok(XBoardCommand::Sd(number()?)),
+			"time" => Ok(XBoardCommand::Time(centiseconds()?)),
+			"otim" => Ok(XBoardCommand::Otim(centiseconds()?)),
+			"result" => {
+				let (result, comment) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+				Ok(XBoardCommand::Result {
+					result: PgnResult::from_token(result).ok_or_else(invalid)?,
+					comment: comment.trim().trim_start_matches('{').trim_end_matches('}').trim().to_string()
+				})
+			},
+			"ping" => Ok(XBoardCommand::Ping(number()?)),
+			"undo" => Ok(XBoardCommand::Undo),
+			"remove" => Ok(XBoardCommand::Remove),
+			"?" => Ok(XBoardCommand::MoveNow),
+			"post" => Ok(XBoardCommand::Post),
+			"nopost" => Ok(XBoardCommand::NoPost),
+			"quit" => Ok(XBoardCommand::Quit),
+			"random" | "hard" | "easy" | "computer" | "name" | "rating" | "ics" | "draw" | "white" | "black" | "variant" | "bk" | "hint" | "." => {
+				Ok(XBoardCommand::Ignored(line.to_string()))
+			},
+			// Without `usermove=1`, moves are sent on their own
+			_ if rest.is_empty() && looks_like_move(command) => Ok(XBoardCommand::UserMove(command.to_string())),
+			_ => Err(invalid())
+		}
+	}
+}
+
+fn looks_like_move(text: &str) -> bool {
+	Move::from_uci(text).is_some()
+		|| (text.len() >= 2 && text.chars().all(|c| "KQRBNabcdefgh12345678x=+#O-0".contains(c)))
+}
+
+/// The engine's thinking output while searching with `post` on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct XBoardThinking {
+	pub depth: u32,
+	/// Centipawns from the engine's point of view, see [`XBOARD_MATE_SCORE`] for mates.
+	pub score: i32,
+	pub time: Duration,
+	pub nodes: u64,
+	/// The principal variation as the engine wrote it, usually SAN.
+	pub pv: String
+}
+
+impl XBoardThinking {
+	/// `ply score time nodes pv`, with the time in centiseconds.
+	pub fn parse(line: &str) -> Option<XBoardThinking> {
+		let mut fields = line.split_whitespace();
+		let depth = fields.next()?.trim_end_matches(['.', '&']).parse().ok()?;
+		let score = fields.next()?.parse().ok()?;
+		let centiseconds = fields.next()?.parse::<u64>().ok()?;
+		let nodes = fields.next()?.parse().ok()?;
+
+		Some(XBoardThinking {
+			depth,
+			score,
+			time: Duration::from_millis(centiseconds * 10),
+			nodes,
+			pv: fields.collect::<Vec<&str>>().join(" ")
+		})
+	}
+
+	/// Thinking output for the built-in search, with the line in SAN.
+	pub fn from_search(position: &Position, info: &SearchInfo) -> XBoardThinking {
+		let score = match mate_in(info.score) {
+			Some(moves) if moves > 0 => XBOARD_MATE_SCORE + moves,
+			Some(moves) => -XBOARD_MATE_SCORE + moves,
+			None => info.score
+		};
+
+		XBoardThinking {
+			depth: info.depth,
+			score,
+			time: info.time,
+			nodes: info.nodes,
+			pv: position.san_line(&info.pv).join(" ")
+		}
+	}
+
+	/// Moves until mate (negative when the engine is getting mated) if the score is a mate score.
+	pub fn mate_in(&self) -> Option<i32> {
+		match self.score {
+			score if score >= XBOARD_MATE_SCORE => Some(score - XBOARD_MATE_SCORE),
+			score if score <= -XBOARD_MATE_SCORE => Some(score + XBOARD_MATE_SCORE),
+			_ => None
+		}
+	}
+}
+
+impl fmt::Display for XBoardThinking {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {} {} {} {}", self.depth, self.score, self.time.as_millis() / 10, self.nodes, self.pv)
+	}
+}
+
+/// A line sent by a CECP engine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum XBoardMessage {
+	/// The `name=value` pairs of a `feature` line.
+	Feature(Vec<(String, String)>),
+	/// The engine's move, in SAN or coordinates.
+	Move(String),
+	Pong(u32),
+	Thinking(XBoardThinking),
+	Result {
+		result: PgnResult,
+		comment: String
+	},
+	Resign,
+	OfferDraw,
+	IllegalMove(String),
+	Error(String),
+	Other(String)
+}
+
+impl XBoardMessage {
+	pub fn parse(line: &str) -> XBoardMessage {
+		let line = line.trim();
+		let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+		let rest = rest.trim();
+
+		if let Some(result) = PgnResult::from_token(command) {
+			return XBoardMessage::Result {
+				result,
+				comment: rest.trim_start_matches('{').trim_end_matches('}').trim().to_string()
+			};
+		}
+
+		match command {
+			"feature" => XBoardMessage::Feature(parse_features(rest)),
+			"move" => XBoardMessage::Move(rest.to_string()),
+			"pong" => match rest.parse() {
+				Ok(number) => XBoardMessage::Pong(number),
+				Err(_) => XBoardMessage::Other(line.to_string())
+			},
+			"resign" => XBoardMessage::Resign,
+			"offer" if rest == "draw" => XBoardMessage::OfferDraw,
+			"Illegal" => XBoardMessage::IllegalMove(rest.split_once(':').map_or(rest, |(_, mv)| mv).trim().to_string()),
+			"Error" => XBoardMessage::Error(rest.to_string()),
+			// Protocol 1 engines announce their moves as "1. ... e5"
+			_ if rest.starts_with("...") => XBoardMessage::Move(rest.trim_start_matches('.').trim().to_string()),
+			_ => match XBoardThinking::parse(line) {
+				Some(thinking) => XBoardMessage::Thinking(thinking),
+				None => XBoardMessage::Other(line.to_string())
+			}
+		}
+	}
+}
+
+// name=value pairs, where values can be quoted strings with spaces in them
+fn parse_features(text: &str) -> Vec<(String, String)> {
+	let mut features = Vec::new();
+	let mut rest = text.trim();
+
+	while let Some((name, value)) = rest.split_once('=') {
+		let (value, remaining) = match value.strip_prefix('"') {
+			Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+			None => value.split_once(char::is_whitespace).unwrap_or((value, ""))
+		};
+
+		features.push((name.trim().to_string(), value.to_string()));
+		rest = remaining.trim();
+	}
+
+	features
+}
+
+/// A chess engine speaking the Chess Engine Communication Protocol, usually running as a child process.
+///
+/// The engine is kept in force mode, so it only thinks when asked to with [`XBoardEngine::go`].
+pub struct XBoardEngine {
+	process: EngineProcess<XBoardMessage>,
+	name: Option<String>,
+	// Features the engine asked for
+	usermove: bool,
+	setboard: bool,
+	san: bool,
+	analysing: bool
+}
+
+impl XBoardEngine {
+	/// Starts the engine binary at `path`. It still needs to be set up with [`XBoardEngine::init`].
+	pub fn spawn(path: &str) -> Result<XBoardEngine, EngineError> {
+		Ok(XBoardEngine::with_process(EngineProcess::spawn(path, XBoardMessage::parse)?))
+	}
+
+	/// An engine talking over any pair of streams, `reader` being its output.
+	pub fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> XBoardEngine {
+		XBoardEngine::with_process(EngineProcess::new(reader, writer, XBoardMessage::parse))
+	}
+
+	fn with_process(process: EngineProcess<XBoardMessage>) -> XBoardEngine {
+		XBoardEngine {
+			process,
+			name: None,
+			usermove: false,
+			setboard: false,
+			san: false,
+			analysing: false
+		}
+	}
+
+	/// The name the engine gave itself with the `myname` feature.
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	/// Sends a command, one line.
+	pub fn send(&mut self, command: &str) -> Result<(), EngineError> {
+		self.process.send(command)
+	}
+
+	/// Switches the engine to protocol version 2 and reads its features, waiting at most
+	/// `timeout` for each. Engines that only know version 1 don't send any, which is fine.
+	pub fn init(&mut self, timeout: Duration) -> Result<(), EngineError> {
+		self.send("xboard")?;
+		self.send("protover 2")?;
+
+		loop {
+			let features = match self.process.recv_timeout(timeout, "protover") {
+				Ok(XBoardMessage::Feature(features)) => features,
+				Ok(_) => continue,
+				Err(EngineError::Timeout(_)) => return Ok(()),
+				Err(error) => return Err(error)
+			};
+
+			let mut done = false;
+			for (name, value) in features {
+				let enabled = value == "1";
+				let accepted = match name.as_str() {
+					"myname" => {
+						self.name = Some(value.clone());
+						true
+					},
+					"usermove" => {
+						self.usermove = enabled;
+						true
+					},
+					"setboard" => {
+						self.setboard = enabled;
+						true
+					},
+					"san" => {
+						self.san = enabled;
+						true
+					},
+					"done" => {
+						done = enabled;
+						true
+					},
+					// Interrupting the engine with signals isn't supported
+					"sigint" | "sigterm" => !enabled,
+					// Only standard chess is played
+					"variants" => value.split(',').any(|variant| variant == "normal"),
+					_ => true
+				};
+
+				self.send(&format!("{} {}", if accepted { "accepted" } else { "rejected" }, name))?;
+			}
+
+			if done {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Sets up the game as the moves played from `start`, leaving the engine waiting in force mode.
+	pub fn set_position(&mut self, start: &Position, moves: &[Move]) -> Result<(), EngineError> {
+		if self.analysing {
+			self.send("exit")?;
+			self.analysing = false;
+		}
+
+		self.send("new")?;
+		self.send("force")?;
+
+		let fen = start.to_fen();
+		if fen != START_FEN {
+			if !self.setboard {
+				return Err(EngineError::InvalidCommand("setboard".to_string()));
+			}
+			self.send(&format!("setboard {}", fen))?;
+		}
+
+		let mut position = start.clone();
+		for mv in moves {
+			let text = if self.san { position.san(*mv) } else { mv.to_string() };
+			if self.usermove {
+				self.send(&format!("usermove {}", text))?;
+			} else {
+				self.send(&text)?;
+			}
+			position.make_move(*mv);
+		}
+
+		Ok(())
+	}
+
+	/// Starts thinking about the side to move. Without any limits the engine analyses until [`XBoardEngine::stop`].
+	/// Node limits can't be given to CECP engines, so they're ignored.
+	pub fn go(&mut self, limits: &SearchLimits) -> Result<(), EngineError> {
+		self.send("post")?;
+
+		if limits.depth.is_none() && limits.time.is_none() {
+			self.analysing = true;
+			return self.send("analyze");
+		}
+
+		if let Some(depth) = limits.depth {
+			self.send(&format!("sd {}", depth))?;
+		}
+		if let Some(time) = limits.time {
+			// Whole seconds only, rounded up
+			self.send(&format!("st {}", time.as_millis().div_ceil(1000).max(1)))?;
+		}
+
+		self.send("go")
+	}
+
+	/// Ends the thinking early. Returns whether a move is still to come, which it is unless the engine was analysing.
+	pub fn stop(&mut self) -> Result<bool, EngineError> {
+		if self.analysing {
+			self.analysing = false;
+			self.send("exit")?;
+			Ok(false)
+		} else {
+			self.send("?")?;
+			Ok(true)
+		}
+	}
+
+	/// The next message if there's one waiting.
+	pub fn try_recv(&self) -> Result<Option<XBoardMessage>, EngineError> {
+		self.process.try_recv()
+	}
+
+	/// Waits for the next message.
+	pub fn recv_timeout(&self, timeout: Duration) -> Result<XBoardMessage, EngineError> {
+		self.process.recv_timeout(timeout, "the last command")
+	}
+}
+
+/// Reads a move in coordinates or SAN, as CECP allows either. None if it isn't legal in `position`.
+pub fn parse_xboard_move(position: &Position, text: &str) -> Option<Move> {
+	match Move::from_uci(text) {
+		Some(mv) if position.is_legal(mv) => Some(mv),
+		_ => position.parse_san(text).ok()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc::Receiver;
+
+	use super::*;
+	use crate::process::fake;
+
+	const TIMEOUT: Duration = Duration::from_secs(5);
+
+	fn fake_engine(script: Vec<(&'static str, Vec<&'static str>)>) -> (XBoardEngine, Receiver<String>) {
+		let (output, input, commands) = fake::fake_engine(script);
+		(XBoardEngine::new(output, input), commands)
+	}
+
+	#[test]
+	fn plays_a_scripted_game() {
+		let (mut engine, commands) = fake_engine(vec![
+			("protover", vec![
+				"feature ping=1 setboard=1 san=1",
+				"feature usermove=1 myname=\"Fake Engine 2\" sigint=1 done=1"
+			]),
+			("go", vec!["3 -25 12 345 Nc6 Nf3 Nf6", "move Nc6"])
+		]);
+
+		engine.init(TIMEOUT).unwrap();
+		assert_eq!(engine.name(), Some("Fake Engine 2"));
+
+		let start = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+		let e4 = Move::from_uci("e2e4").unwrap();
+		engine.set_position(&start, &[e4]).unwrap();
+		engine.go(&SearchLimits::time(Duration::from_millis(1500))).unwrap();
+
+		let thinking = match engine.recv_timeout(TIMEOUT).unwrap() {
+			XBoardMessage::Thinking(thinking) => thinking,
+			message => panic!("unexpected {:?}", message)
+		};
+		assert_eq!(thinking.depth, 3);
+		assert_eq!(thinking.score, -25);
+		assert_eq!(thinking.time, Duration::from_millis(120));
+		assert_eq!(thinking.nodes, 345);
+		assert_eq!(thinking.pv, "Nc6 Nf3 Nf6");
+
+		let text = match engine.recv_timeout(TIMEOUT).unwrap() {
+			XBoardMessage::Move(text) => text,
+			message => panic!("unexpected {:?}", message)
+		};
+		assert_eq!(parse_xboard_move(&start.play(e4), &text), Move::from_uci("b8c6"));
+
+		let sent = commands.try_iter().collect::<Vec<String>>();
+		assert_eq!(sent, vec![
+			"xboard",
+			"protover 2",
+			"accepted ping",
+			"accepted setboard",
+			"accepted san",
+			"accepted usermove",
+			"accepted myname",
+			"rejected sigint",
+			"accepted done",
+			"new",
+			"force",
+			"setboard rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/RNBQKBNR w KQkq - 0 1",
+			"usermove e4",
+			"post",
+			"st 2",
+			"go"
+		]);
+	}
+
+	#[test]
+	fn accepts_protocol_1_engines() {
+		let (mut engine, commands) = fake_engine(Vec::new());
+		engine.init(Duration::from_millis(50)).unwrap();
+
+		engine.set_position(&Position::start(), &[Move::from_uci("g1f3").unwrap()]).unwrap();
+		engine.go(&SearchLimits::default()).unwrap();
+		assert!(!engine.stop().unwrap());
+
+		let sent = commands.try_iter().collect::<Vec<String>>();
+		assert_eq!(sent, vec!["xboard", "protover 2", "new", "force", "g1f3", "post", "analyze", "exit"]);
+
+		// Setting up a position needs setboard
+		assert!(engine.set_position(&Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(), &[]).is_err());
+	}
+
+	#[test]
+	fn parses_gui_commands() {
+		assert_eq!(XBoardCommand::parse("usermove e7e8q").unwrap(), XBoardCommand::UserMove("e7e8q".to_string()));
+		assert_eq!(XBoardCommand::parse("e2e4").unwrap(), XBoardCommand::UserMove("e2e4".to_string()));
+		assert_eq!(XBoardCommand::parse("Nf3").unwrap(), XBoardCommand::UserMove("Nf3".to_string()));
+		assert_eq!(XBoardCommand::parse("level 40 5 0").unwrap(), XBoardCommand::Level {
+			moves: 40,
+			base: Duration::from_secs(300),
+			increment: Duration::ZERO
+		});
+		assert_eq!(XBoardCommand::parse("level 0 0:30 2").unwrap(), XBoardCommand::Level {
+			moves: 0,
+			base: Duration::from_secs(30),
+			increment: Duration::from_secs(2)
+		});
+		assert_eq!(XBoardCommand::parse("time 1234").unwrap(), XBoardCommand::Time(Duration::from_millis(12340)));
+		assert_eq!(XBoardCommand::parse("result 1-0 {White mates}").unwrap(), XBoardCommand::Result {
+			result: PgnResult::WhiteWins,
+			comment: "White mates".to_string()
+		});
+		assert_eq!(XBoardCommand::parse("setboard 4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(), XBoardCommand::SetBoard(Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap()));
+		assert!(XBoardCommand::parse("setboard nonsense").is_err());
+		assert!(XBoardCommand::parse("level 40").is_err());
+		assert!(XBoardCommand::parse("frobnicate").is_err());
+	}
+
+	#[test]
+	fn parses_engine_lines() {
+		assert_eq!(XBoardMessage::parse("move e7e5"), XBoardMessage::Move("e7e5".to_string()));
+		assert_eq!(XBoardMessage::parse("1. ... e5"), XBoardMessage::Move("e5".to_string()));
+		assert_eq!(XBoardMessage::parse("Illegal move: e2e5"), XBoardMessage::IllegalMove("e2e5".to_string()));
+		assert_eq!(XBoardMessage::parse("Illegal move (no piece there): e2e5"), XBoardMessage::IllegalMove("e2e5".to_string()));
+		assert_eq!(XBoardMessage::parse("0-1 {White resigns}"), XBoardMessage::Result {
+			result: PgnResult::BlackWins,
+			comment: "White resigns".to_string()
+		});
+		assert_eq!(XBoardMessage::parse("offer draw"), XBoardMessage::OfferDraw);
+		assert_eq!(XBoardMessage::parse("pong 7"), XBoardMessage::Pong(7));
+
+		let thinking = match XBoardMessage::parse("12 100003 250 987654 Qh5 Ke7 Qe5#") {
+			XBoardMessage::Thinking(thinking) => thinking,
+			message => panic!("unexpected {:?}", message)
+		};
+		assert_eq!(thinking.mate_in(), Some(3));
+		assert_eq!(XBoardMessage::parse(&thinking.to_string()), XBoardMessage::Thinking(thinking));
+	}
+}
@@ -0,0 +1,155 @@
+use crate::{CastlingRights, PieceColor, PieceType, Square};
+
+// Random numbers for each piece on each square, black to move, each castling right and each
+// en passant file. A position's hash is all the ones that apply to it xored together, so a move
+// only has to xor in what it changes
+struct ZobristKeys {
+	pieces: [[[u64; 64]; 6]; 2],
+	black_to_move: u64,
+	castling: [u64; 4],
+	en_passant: [u64; 8]
+}
+
+// Generated at compile time with splitmix64 so hashes stay the same between runs, which
+// anything stored by hash (such as an opening book) relies on
+const KEYS: ZobristKeys = {
+	const fn next(state: &mut u64) -> u64 {
+		*state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = *state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	let mut state = 0;
+	let mut keys = ZobristKeys {
+		pieces: [[[0; 64]; 6]; 2],
+		black_to_move: 0,
+		castling: [0; 4],
+		en_passant: [0; 8]
+	};
+
+	let mut color = 0;
+	while color < 2 {
+		let mut piece_type = 0;
+		while piece_type < 6 {
+			let mut square = 0;
+			while square < 64 {
+				keys.pieces[color][piece_type][square] = next(&mut state);
+				square += 1;
+			}
+			piece_type += 1;
+		}
+		color += 1;
+	}
+
+	keys.black_to_move = next(&mut state);
+
+	let mut i = 0;
+	while i < 4 {
+		keys.castling[i] = next(&mut state);
+		i += 1;
+	}
+
+	let mut i = 0;
+	while i < 8 {
+		keys.en_passant[i] = next(&mut state);
+		i += 1;
+	}
+
+	keys
+};
+
+pub(crate) fn piece_key(color: PieceColor, piece_type: PieceType, square: Square) -> u64 {
+	KEYS.pieces[color.index()][piece_type.index()][square.index()]
+}
+
+pub(crate) fn black_to_move_key() -> u64 {
+	KEYS.black_to_move
+}
+
+pub(crate) fn castling_key(castling_rights: CastlingRights) -> u64 {
+	[
+		castling_rights.white_king_side,
+		castling_rights.white_queen_side,
+		castling_rights.black_king_side,
+		castling_rights.black_queen_side
+	]
+	.iter()
+	.zip(KEYS.castling)
+	.filter(|(allowed, _)| **allowed)
+	.fold(0, |hash, (_, key)| hash ^ key)
+}
+
+pub(crate) fn en_passant_key(file: u8) -> u64 {
+	KEYS.en_passant[file as usize]
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	#[test]
+	fn hashes_follow_moves() {
+		let mut position = Position::start();
+		let start_hash = position.zobrist_hash();
+
+		// Knights out and back gets to the same position through different moves
+		let moves = ["g1f3", "g8f6", "f3g1", "f6g8"].map(|mv| Move::from_uci(mv).unwrap());
+		let mut undos = Vec::new();
+		for mv in moves {
+			undos.push(position.make_move(mv));
+			assert_eq!(position.zobrist_hash(), Position::from_fen(&position.to_fen()).unwrap().zobrist_hash());
+		}
+		assert_eq!(position.zobrist_hash(), start_hash);
+
+		for (mv, undo) in moves.into_iter().zip(undos).rev() {
+			position.unmake_move(mv, undo);
+		}
+		assert_eq!(position, Position::start());
+		assert_eq!(position.zobrist_hash(), start_hash);
+
+		// Castling rights and the side to move make a difference
+		let no_castling = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1").unwrap();
+		let black_to_move = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+		assert_ne!(no_castling.zobrist_hash(), start_hash);
+		assert_ne!(black_to_move.zobrist_hash(), start_hash);
+	}
+
+	// Every position in the tree should hash the same as a fresh copy set up from its FEN
+	fn check_tree(position: &mut Position, depth: u32) {
+		assert_eq!(position.zobrist_hash(), Position::from_fen(&position.to_fen()).unwrap().zobrist_hash(), "{}", position.to_fen());
+		if depth == 0 {
+			return;
+		}
+
+		for mv in position.legal_moves() {
+			let undo = position.make_move(mv);
+			check_tree(position, depth - 1);
+			position.unmake_move(mv, undo);
+		}
+	}
+
+	#[test]
+	fn hashes_stay_in_sync() {
+		// Castling, promotions and en passant all come up within two moves of these
+		for fen in [
+			"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+			"r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"
+		] {
+			check_tree(&mut Position::from_fen(fen).unwrap(), 2);
+		}
+	}
+
+	#[test]
+	fn en_passant_counts_only_when_possible() {
+		// No black pawn next to e4, so e3 doesn't make a difference
+		let with_square = Position::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+		let without = Position::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+		assert_eq!(with_square.zobrist_hash(), without.zobrist_hash());
+
+		let with_square = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+		let without = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+		assert_ne!(with_square.zobrist_hash(), without.zobrist_hash());
+	}
+}
@@ -0,0 +1,111 @@
+use bevy::{prelude::*, tasks::{AsyncComputeTaskPool, Task}};
+use chess_core::{mate_in, search, Move, SearchLimits};
+use futures_lite::future;
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+use crate::{board::*, clock::*, history::*, menu::*, replay::*};
+
+// How long the computer thinks about each move
+#[derive(Resource)]
+pub struct AiSettings {
+	pub think_time: Duration
+}
+
+impl Default for AiSettings {
+	fn default() -> Self {
+		AiSettings {
+			think_time: Duration::from_secs(1)
+		}
+	}
+}
+
+// The search running in the background, so the board keeps rendering while the computer thinks
+#[derive(Default, Resource)]
+pub struct AiTask {
+	task: Option<Task<Option<Move>>>,
+	// Dropping the task doesn't end a search that's already running, this does
+	stop: Arc<AtomicBool>
+}
+
+impl AiTask {
+	pub fn cancel(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		self.task = None;
+	}
+}
+
+impl Drop for AiTask {
+	fn drop(&mut self) {
+		self.cancel();
+	}
+}
+
+fn start_thinking(turn: Res<PlayerTurn>, players: Res<Players>, position: Res<GamePosition>, outcome: Res<GameOutcome>, settings: Res<AiSettings>, clock: Res<GameClock>, mut ai_task: ResMut<AiTask>) {
+	if !turn.is_changed() {
+		return;
+	}
+
+	// A takeback can hand the turn back to the human while the computer is still thinking
+	if players.get(turn.0) != Player::Computer {
+		ai_task.cancel();
+		return;
+	}
+
+	if ai_task.task.is_some() || outcome.0.is_some() {
+		return;
+	}
+
+	let position = position.0.clone();
+	// On the clock, the time to think comes out of what's left on it
+	let limits = clock.search_limits(&turn).unwrap_or(SearchLimits::time(settings.think_time));
+
+	let stop = Arc::new(AtomicBool::new(false));
+	ai_task.stop = stop.clone();
+	ai_task.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+		let result = search(&position, limits, &stop, |info| {
+			let score = match mate_in(info.score) {
+				Some(moves) => format!("mate {}", moves),
+				None => format!("{} cp", info.score)
+			};
+			let pv = info.pv.iter().map(|mv| mv.to_string()).collect::<Vec<String>>().join(" ");
+			debug!("depth {} score {} nodes {} pv {}", info.depth, score, info.nodes, pv);
+		});
+
+		result.best_move
+	}));
+}
+
+// Play the computer's move through the same path as a human one once the search is done
+fn finish_thinking(mut ai_task: ResMut<AiTask>, mut move_event: EventWriter<MoveEvent>) {
+	let best_move = if let Some(task) = &mut ai_task.task {
+		match future::block_on(future::poll_once(task)) {
+			Some(best_move) => best_move,
+			None => return
+		}
+	} else {
+		return;
+	};
+
+	ai_task.task = None;
+	if let Some(mv) = best_move {
+		move_event.send(MoveEvent(mv));
+	}
+}
+
+// A search for a game that's over or left has nothing to play anymore
+fn cancel_thinking(mut ai_task: ResMut<AiTask>) {
+	ai_task.cancel();
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<AiSettings>()
+			.init_resource::<AiTask>()
+			.add_system(cancel_thinking.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(cancel_thinking.in_schedule(OnEnter(AppState::GameOver)))
+			.add_system(cancel_thinking.in_schedule(OnEnter(AppState::MainMenu)))
+			.add_system(start_thinking.after(game_over).after(press_clock).run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(finish_thinking.after(take_back).after(redo).run_if(not_replaying).run_if(not_browsing).run_if(in_state(AppState::InGame)));
+	}
+}
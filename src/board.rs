@@ -1,6 +1,7 @@
-use bevy::{prelude::*, app::AppExit, ecs::event::{EventReader, EventWriter}};
+use bevy::{prelude::*, ecs::event::{EventReader, EventWriter}};
 use bevy_mod_picking::prelude::*;
-use crate::pieces::*;
+use chess_core::{GameResult, Move, Position};
+use crate::{ai::*, clock::*, history::*, menu::*, pieces::*, replay::*};
 
 #[derive(Component)]
 pub struct Square {
@@ -72,12 +73,101 @@ struct SelectedPiece {
 	entity: Option<Entity>
 }
 
-fn color_squares(selected_square: Res<SelectedSquare>, hover_square: Res<HoverSquare>, materials: Res<SquareMaterials>, mut query: Query<(Entity, &Square, &mut Handle<StandardMaterial>)>) {
+// Where the selected piece can go, and whether it captures there
+#[derive(Default, Resource)]
+struct LegalDestinations(Vec<(chess_core::Square, bool)>);
+
+impl LegalDestinations {
+	fn get(&self, square: chess_core::Square) -> Option<bool> {
+		self.0.iter().find(|(destination, _)| *destination == square).map(|(_, capture)| *capture)
+	}
+}
+
+fn find_legal_destinations(selected_piece: Res<SelectedPiece>, position: Res<GamePosition>, pieces_query: Query<&Piece>, mut destinations: ResMut<LegalDestinations>) {
+	if !selected_piece.is_changed() {
+		return;
+	}
+
+	destinations.0 = match selected_piece.entity.and_then(|entity| pieces_query.get(entity).ok()) {
+		Some(piece) => position.0.legal_moves()
+			.into_iter()
+			.filter(|mv| mv.from == piece.square())
+			.map(|mv| (mv.to, position.0.captured_square(mv).is_some()))
+			.collect(),
+		None => Vec::new()
+	};
+}
+
+// A square clicked that the selected piece can't go to, flashed for a moment
+#[derive(Resource)]
+struct IllegalClick {
+	entity: Option<Entity>,
+	timer: Timer
+}
+
+impl Default for IllegalClick {
+	fn default() -> Self {
+		IllegalClick {
+			entity: None,
+			timer: Timer::from_seconds(0.4, TimerMode::Once)
+		}
+	}
+}
+
+fn fade_illegal_click(time: Res<Time>, mut illegal_click: ResMut<IllegalClick>) {
+	if illegal_click.entity.is_some() && illegal_click.timer.tick(time.delta()).finished() {
+		illegal_click.entity = None;
+	}
+}
+
+// Whether the pieces giving check are marked along with the king, toggled with C
+#[derive(Default, Resource)]
+struct ShowCheckers(bool);
+
+fn toggle_checkers(keys: Res<Input<KeyCode>>, mut show_checkers: ResMut<ShowCheckers>) {
+	if keys.just_pressed(KeyCode::C) {
+		show_checkers.0 = !show_checkers.0;
+	}
+}
+
+// What the player is doing with the mouse comes first, then where the selected piece can go,
+// then a king in check and what checks it, and last the move just played
+fn color_squares(selected_square: Res<SelectedSquare>, hover_square: Res<HoverSquare>, destinations: Res<LegalDestinations>, illegal_click: Res<IllegalClick>, position: Res<GamePosition>, history: Res<MoveHistory>, browsing: Res<Browsing>, show_checkers: Res<ShowCheckers>, materials: Res<SquareMaterials>, mut query: Query<(Entity, &Square, &mut Handle<StandardMaterial>)>) {
+	// Looking back through the game shows the checks and last move of the ply on the board
+	let shown = browsing.position(&position);
+	let checkers = shown.checkers();
+	let checked_king = if checkers.is_empty() {
+		None
+	} else {
+		shown.king_square(shown.side_to_move())
+	};
+	let last_move = browsing.ply(&history).checked_sub(1).map(|ply| &history.moves[ply]);
+
 	for (entity, square, mut material) in query.iter_mut() {
-		*material = if Some(entity) == hover_square.entity {
+		let board_square = chess_core::Square::new(square.x, square.y);
+
+		*material = if Some(entity) == illegal_click.entity {
+			materials.illegal_color.clone()
+		} else if Some(entity) == hover_square.entity {
 			materials.highlight_color.clone()
 		} else if Some(entity) == selected_square.entity {
 			materials.selected_color.clone()
+		} else if let Some(capture) = destinations.get(board_square) {
+			if capture {
+				materials.capture_color.clone()
+			} else {
+				materials.move_color.clone()
+			}
+		} else if Some(board_square) == checked_king {
+			materials.check_color.clone()
+		} else if show_checkers.0 && checkers.contains(board_square) {
+			materials.checker_color.clone()
+		} else if last_move.is_some_and(|mv| mv.from == board_square || mv.to == board_square) {
+			if square.is_white() {
+				materials.last_move_white_color.clone()
+			} else {
+				materials.last_move_black_color.clone()
+			}
 		} else if square.is_white() {
 			materials.white_color.clone()
 		} else {
@@ -93,24 +183,56 @@ impl Plugin for BoardPlugin {
 		app.init_resource::<SelectedSquare>()
 			.init_resource::<HoverSquare>()
 			.init_resource::<SelectedPiece>()
+			.init_resource::<LegalDestinations>()
+			.init_resource::<IllegalClick>()
+			.init_resource::<ShowCheckers>()
+			.init_resource::<GamePosition>()
 			.init_resource::<PlayerTurn>()
+			.init_resource::<Players>()
+			.init_resource::<PendingPromotion>()
+			.init_resource::<GameOutcome>()
+			.init_resource::<ResignPrompt>()
 			.init_resource::<SquareMaterials>()
 			.add_event::<ResetSelectedEvent>()
-			.add_startup_system(create_board)
+			.add_event::<GameOverEvent>()
+			.add_event::<PromotionEvent>()
+			.add_event::<MoveEvent>()
+			.add_event::<TakebackEvent>()
+			.add_event::<RedoEvent>()
+			.add_system(create_board.in_schedule(OnExit(AppState::MainMenu)))
+			.add_system(despawn_all::<Square>.in_schedule(OnEnter(AppState::MainMenu)))
+			.add_system(reset_game.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(clear_selection.in_schedule(OnEnter(AppState::Setup)))
 			.add_system(color_squares)
-			.add_system(select_piece)
-			.add_system(move_piece.before(select_piece))
+			.add_system(find_legal_destinations.after(select_piece).after(reset_selected))
+			.add_system(fade_illegal_click)
+			.add_system(toggle_checkers)
+			.add_system(select_piece.run_if(not_replaying).run_if(not_browsing).run_if(in_state(AppState::InGame)))
+			.add_system(move_piece.before(select_piece).run_if(not_replaying).run_if(not_browsing).run_if(in_state(AppState::InGame)))
+			.add_system(make_move.after(move_piece).run_if(not_replaying).run_if(not_browsing).run_if(in_state(AppState::InGame)))
 			.add_system(reset_selected)
-			.add_system(despawn_taken_pieces);
+			.add_system(despawn_taken_pieces)
+			.add_system(promote_pawn.run_if(in_state(AppState::InGame)))
+			.add_system(print_fen.run_if(game_on_board))
+			.add_system(resign.run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(takeback_keys.run_if(not_replaying).run_if(playing_or_over))
+			.add_system(take_back.after(takeback_keys).run_if(not_replaying).run_if(not_browsing).run_if(playing_or_over))
+			.add_system(redo.after(takeback_keys).before(game_over).run_if(not_replaying).run_if(not_browsing).run_if(playing_or_over))
+			.add_system(game_over.after(make_move).after(promote_pawn).after(resign).run_if(in_state(AppState::InGame)));
 	}
 }
 
+// The rules side of the game, kept in step with the piece entities
+#[derive(Default, Resource)]
+pub struct GamePosition(pub Position);
+
 #[derive(Resource)]
 pub struct PlayerTurn(pub PieceColor);
 
-impl Default for PlayerTurn {
-	fn default() -> Self {
-		Self(PieceColor::White)
+// The first turn is whoever moves in the starting position
+impl FromWorld for PlayerTurn {
+	fn from_world(world: &mut World) -> Self {
+		Self(world.resource::<GamePosition>().0.side_to_move())
 	}
 }
 
@@ -123,8 +245,58 @@ impl PlayerTurn {
 	}
 }
 
-fn select_piece(selected_square: Res<SelectedSquare>, mut selected_piece: ResMut<SelectedPiece>, turn: Res<PlayerTurn>, squares_query: Query<&Square>, pieces_query: Query<(Entity, &Piece)>) {
-	if !selected_square.is_changed() {
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Player {
+	Human,
+	Computer,
+	// An external UCI engine
+	Engine
+}
+
+// Who plays each color, humans by default
+#[derive(Resource)]
+pub struct Players {
+	pub white: Player,
+	pub black: Player
+}
+
+impl Default for Players {
+	fn default() -> Self {
+		Players {
+			white: Player::Human,
+			black: Player::Human
+		}
+	}
+}
+
+impl Players {
+	pub fn get(&self, color: PieceColor) -> Player {
+		match color {
+			PieceColor::White => self.white,
+			PieceColor::Black => self.black
+		}
+	}
+
+	pub fn set(&mut self, color: PieceColor, player: Player) {
+		match color {
+			PieceColor::White => self.white = player,
+			PieceColor::Black => self.black = player
+		}
+	}
+
+	// Whether either color is played by `player`
+	pub fn has(&self, player: Player) -> bool {
+		self.white == player || self.black == player
+	}
+
+	pub fn swap(&mut self) {
+		std::mem::swap(&mut self.white, &mut self.black);
+	}
+}
+
+fn select_piece(selected_square: Res<SelectedSquare>, mut selected_piece: ResMut<SelectedPiece>, turn: Res<PlayerTurn>, players: Res<Players>, pending_promotion: Res<PendingPromotion>, outcome: Res<GameOutcome>, squares_query: Query<&Square>, pieces_query: Query<(Entity, &Piece)>) {
+	// Only humans move pieces by clicking on them, until the game is over
+	if !selected_square.is_changed() || pending_promotion.0.is_some() || players.get(turn.0) != Player::Human || outcome.0.is_some() {
 		return;
 	}
 
@@ -152,8 +324,10 @@ fn select_piece(selected_square: Res<SelectedSquare>, mut selected_piece: ResMut
 	}
 }
 
-fn move_piece(mut commands: Commands, selected_square: Res<SelectedSquare>, selected_piece: Res<SelectedPiece>, mut turn: ResMut<PlayerTurn>, squares_query: Query<&Square>, mut pieces_query: Query<(Entity, &mut Piece)>, mut reset_selected_event: EventWriter<ResetSelectedEvent>) {
-	if !selected_square.is_changed() {
+// Turn the clicks on a piece and then a square into a move. Clicking another of the player's
+// pieces selects it instead, and the selected one again lets go of it
+fn move_piece(selected_square: Res<SelectedSquare>, mut selected_piece: ResMut<SelectedPiece>, pending_promotion: Res<PendingPromotion>, destinations: Res<LegalDestinations>, mut illegal_click: ResMut<IllegalClick>, squares_query: Query<&Square>, pieces_query: Query<&Piece>, mut move_event: EventWriter<MoveEvent>, mut reset_selected_event: EventWriter<ResetSelectedEvent>) {
+	if !selected_square.is_changed() || pending_promotion.0.is_some() {
 		return;
 	}
 	
@@ -170,45 +344,152 @@ fn move_piece(mut commands: Commands, selected_square: Res<SelectedSquare>, sele
 	};
 
 	if let Some(selected_piece_entity) = selected_piece.entity {
-		let pieces_vec = pieces_query
-			.iter_mut()
-			.map(|(_, piece)| *piece)
-			.collect::<Vec<Piece>>();
-
-		let pieces_entity_vec = pieces_query
-			.iter_mut()
-			.map(|(entity, piece)| (entity, *piece))
-			.collect::<Vec<(Entity, Piece)>>();
-
-		// Move the selected piece to the selected square
-		let mut piece = if let Ok((_piece_entity, piece)) = pieces_query.get_mut(selected_piece_entity) {
-			piece
+		let piece = if let Ok(piece) = pieces_query.get(selected_piece_entity) {
+			*piece
 		} else {
 			return;
 		};
 
-		if piece.is_move_valid((square.x, square.y), pieces_vec) {
-			// Check if a piece of ther opposite color exists in this square and despawn it
-			for (other_entity, other_piece) in pieces_entity_vec {
-				if other_piece.x == square.x && other_piece.y == square.y && other_piece.color != piece.color {
-					// Mark the piece as taken
-					commands.entity(other_entity).insert(Taken);
-				}
+		let to = chess_core::Square::new(square.x, square.y);
+		if destinations.get(to).is_some() {
+			move_event.send(MoveEvent(Move::new(piece.square(), to)));
+			reset_selected_event.send(ResetSelectedEvent);
+		} else if to == piece.square() {
+			reset_selected_event.send(ResetSelectedEvent);
+		} else if pieces_query.iter().any(|other| other.square() == to && other.color == piece.color) {
+			// `select_piece` picks up the piece on the square
+			selected_piece.entity = None;
+		} else {
+			illegal_click.entity = Some(square_entity);
+			illegal_click.timer.reset();
+		}
+	}
+}
+
+// A move from whoever's turn it is, human or computer. Illegal moves are ignored.
+// Promotions without a piece let the player pick one before the turn ends
+pub struct MoveEvent(pub Move);
+
+pub fn make_move(mut commands: Commands, mut event_reader: EventReader<MoveEvent>, mut turn: ResMut<PlayerTurn>, mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, clock: Res<GameClock>, mut pending_promotion: ResMut<PendingPromotion>, outcome: Res<GameOutcome>, piece_assets: Res<PieceAssets>, mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>, mut game_over_event: EventWriter<GameOverEvent>) {
+	for MoveEvent(mv) in event_reader.iter() {
+		if pending_promotion.0.is_some() || outcome.0.is_some() {
+			continue;
+		}
+
+		// Promotions are checked as a queen, the player picks the actual piece afterwards
+		let choose_promotion = mv.promotion.is_none() && position.0.is_promotion(*mv);
+		let checked_move = if choose_promotion {
+			Move::with_promotion(mv.from, mv.to, PieceType::Queen)
+		} else {
+			*mv
+		};
+
+		if !position.0.is_legal(checked_move) {
+			continue;
+		}
+
+		let moved_entity = move_piece_entities(&mut commands, &position.0, checked_move, &mut pieces_query);
+
+		if choose_promotion {
+			// The turn only ends once the player has picked a piece for the pawn
+			pending_promotion.0 = moved_entity.map(|entity| (entity, checked_move));
+		} else {
+			// Swap the pawn for the piece it was promoted to
+			if let (Some(entity), Some(piece_type)) = (moved_entity, mv.promotion) {
+				commands.entity(entity).despawn_recursive();
+				spawn_piece(&mut commands, &piece_assets, turn.0, piece_type, (mv.to.rank(), mv.to.file()));
 			}
 
+			end_turn(*mv, &mut position, &mut history, &clock, &mut turn, &mut game_over_event);
+		}
+	}
+}
+
+// Play a move on the position once the piece entities reflect it, and hand over the turn
+fn end_turn(mv: Move, position: &mut GamePosition, history: &mut MoveHistory, clock: &GameClock, turn: &mut PlayerTurn, game_over_event: &mut EventWriter<GameOverEvent>) {
+	history.play(&mut position.0, mv, clock);
+
+	// Change turn
+	turn.change();
+
+	// The game is over if the next player can't move, or it's a draw nobody has to claim
+	if let Some(result) = position.0.game_result_with_history(&history.hashes) {
+		game_over_event.send(GameOverEvent(result));
+	}
+}
+
+// Mirror a move on the piece entities, before it's made on the position.
+// Returns the entity that moved, which is still a pawn if the move is a promotion
+pub fn move_piece_entities(commands: &mut Commands, position: &Position, mv: Move, pieces_query: &mut Query<(Entity, &mut Piece), Without<Taken>>) -> Option<Entity> {
+	let captured_square = position.captured_square(mv);
+	let rook_move = position.castling_rook_move(mv);
+	let mut moved_entity = None;
+
+	for (entity, mut piece) in pieces_query.iter_mut() {
+		let square = piece.square();
+
+		if Some(square) == captured_square {
+			// Mark the piece as taken
+			commands.entity(entity).insert(Taken);
+		} else if square == mv.from {
 			// Move piece
-			piece.x = square.x;
-			piece.y = square.y;
+			piece.x = mv.to.rank();
+			piece.y = mv.to.file();
+			moved_entity = Some(entity);
+		} else if let Some((rook_from, rook_to)) = rook_move {
+			// When castling the rook moves along with the king
+			if square == rook_from {
+				piece.x = rook_to.rank();
+				piece.y = rook_to.file();
+			}
+		}
+	}
+
+	moved_entity
+}
+
+// Mirror a move on the piece entities, promotion included, before it's made on the position
+pub fn play_move_entities(commands: &mut Commands, piece_assets: &PieceAssets, position: &Position, mv: Move, pieces_query: &mut Query<(Entity, &mut Piece), Without<Taken>>) {
+	let moved_entity = move_piece_entities(commands, position, mv, pieces_query);
 
-			// Change turn
-			turn.change();
+	// Swap the pawn for the piece it was promoted to
+	if let (Some(entity), Some(piece_type)) = (moved_entity, mv.promotion) {
+		commands.entity(entity).despawn_recursive();
+		spawn_piece(commands, piece_assets, position.side_to_move(), piece_type, (mv.to.rank(), mv.to.file()));
+	}
+}
+
+// Take a move back on the piece entities, after it's been unmade on the position.
+// `captured` is the piece the move took, which gets spawned again
+pub fn unmove_piece_entities(commands: &mut Commands, piece_assets: &PieceAssets, position: &Position, mv: Move, captured: Option<(chess_core::Square, chess_core::Piece)>, pieces_query: &mut Query<(Entity, &mut Piece), Without<Taken>>) {
+	let rook_move = position.castling_rook_move(mv);
+
+	for (entity, mut piece) in pieces_query.iter_mut() {
+		let square = piece.square();
+
+		if square == mv.to {
+			if mv.promotion.is_some() {
+				// Promoted pieces turn back into a pawn
+				commands.entity(entity).despawn_recursive();
+				spawn_piece(commands, piece_assets, piece.color, PieceType::Pawn, (mv.from.rank(), mv.from.file()));
+			} else {
+				piece.x = mv.from.rank();
+				piece.y = mv.from.file();
+			}
+		} else if let Some((rook_from, rook_to)) = rook_move {
+			if square == rook_to {
+				piece.x = rook_from.rank();
+				piece.y = rook_from.file();
+			}
 		}
+	}
 
-		reset_selected_event.send(ResetSelectedEvent);
+	if let Some((square, captured)) = captured {
+		spawn_piece(commands, piece_assets, captured.color, captured.piece_type, (square.rank(), square.file()));
 	}
 }
 
-struct ResetSelectedEvent;
+pub struct ResetSelectedEvent;
 
 fn reset_selected(mut event_reader: EventReader<ResetSelectedEvent>, mut selected_square: ResMut<SelectedSquare>, mut selected_piece: ResMut<SelectedPiece>) {
 	for _event in event_reader.iter() {
@@ -218,29 +499,243 @@ fn reset_selected(mut event_reader: EventReader<ResetSelectedEvent>, mut selecte
 }
 
 #[derive(Component)]
-struct Taken;
-
-fn despawn_taken_pieces(mut commands: Commands, mut app_exit_events: EventWriter<AppExit>, query: Query<(Entity, &Piece, &Taken)>) {
-	for (entity, piece, _taken) in query.iter() {
-		// If the king is taken, we should exit
-		if piece.piece_type == PieceType::King {
-			println!("{} won! Thanks for playing!", match piece.color {
-				PieceColor::White => "Black",
-				PieceColor::Black => "White"
-			});
-
-			app_exit_events.send(AppExit);
-		}
+pub struct Taken;
 
+fn despawn_taken_pieces(mut commands: Commands, query: Query<(Entity, &Taken)>) {
+	for (entity, _taken) in query.iter() {
 		// Despawn piece and children
 		commands.entity(entity).despawn_recursive();
 	}
 }
 
+// Pawn waiting on the player to choose what it gets promoted to, along with the move that got it there
+#[derive(Default, Resource)]
+pub struct PendingPromotion(pub Option<(Entity, Move)>);
+
+pub struct PromotionEvent(pub PieceType);
+
+pub fn promote_pawn(mut commands: Commands, mut event_reader: EventReader<PromotionEvent>, mut pending_promotion: ResMut<PendingPromotion>, mut turn: ResMut<PlayerTurn>, mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, clock: Res<GameClock>, piece_assets: Res<PieceAssets>, pieces_query: Query<&Piece>, mut game_over_event: EventWriter<GameOverEvent>) {
+	for PromotionEvent(piece_type) in event_reader.iter() {
+		let (pawn_entity, mv) = if let Some(pending) = pending_promotion.0 {
+			pending
+		} else {
+			continue;
+		};
+
+		let pawn = if let Ok(pawn) = pieces_query.get(pawn_entity) {
+			*pawn
+		} else {
+			continue;
+		};
+
+		// Replace the pawn with the chosen piece
+		commands.entity(pawn_entity).despawn_recursive();
+		spawn_piece(&mut commands, &piece_assets, pawn.color, *piece_type, (pawn.x, pawn.y));
+		pending_promotion.0 = None;
+
+		let mv = Move::with_promotion(mv.from, mv.to, *piece_type);
+		end_turn(mv, &mut position, &mut history, &clock, &mut turn, &mut game_over_event);
+	}
+}
+
+// Rebuild the position from the piece entities and the turn. Castling rights, en passant
+// and the move counters aren't stored on the entities so they come from `position`
+pub fn position_from_entities<'a>(pieces: impl Iterator<Item = &'a Piece>, turn: &PlayerTurn, position: &Position) -> Position {
+	let mut entities_position = Position::empty();
+	for piece in pieces {
+		entities_position.set_piece(piece.square(), Some(chess_core::Piece::new(piece.color, piece.piece_type)));
+	}
+
+	entities_position.set_side_to_move(turn.0);
+	entities_position.set_castling_rights(position.castling_rights());
+	entities_position.set_en_passant(position.en_passant());
+	entities_position.set_halfmove_clock(position.halfmove_clock());
+	entities_position.set_fullmove_number(position.fullmove_number());
+
+	entities_position
+}
+
+// Print the current board as FEN when F is pressed
+fn print_fen(keys: Res<Input<KeyCode>>, turn: Res<PlayerTurn>, position: Res<GamePosition>, pieces_query: Query<&Piece, Without<Taken>>) {
+	if keys.just_pressed(KeyCode::F) {
+		info!("{}", position_from_entities(pieces_query.iter(), &turn, &position.0).to_fen());
+	}
+}
+
+// How the game ended, once it has. Moves aren't taken anymore after that
+#[derive(Default, Resource)]
+pub struct GameOutcome(pub Option<GameResult>);
+
+pub struct GameOverEvent(pub GameResult);
+
+pub fn game_over(mut event_reader: EventReader<GameOverEvent>, mut outcome: ResMut<GameOutcome>, mut next_state: ResMut<NextState<AppState>>) {
+	for GameOverEvent(result) in event_reader.iter() {
+		// Only the first way the game ended counts, e.g. a draw claimed on the same frame as a move
+		if outcome.0.is_none() {
+			info!("{}", result);
+			outcome.0 = Some(*result);
+			next_state.set(AppState::GameOver);
+		}
+	}
+}
+
+// The first press of R asks for a second one to resign, for as long as the timer runs
+#[derive(Default, Resource)]
+pub struct ResignPrompt(pub Option<Timer>);
+
+// The human to move gives up by pressing R twice
+fn resign(keys: Res<Input<KeyCode>>, time: Res<Time>, turn: Res<PlayerTurn>, players: Res<Players>, outcome: Res<GameOutcome>, mut prompt: ResMut<ResignPrompt>, mut game_over_event: EventWriter<GameOverEvent>) {
+	// The prompt is for the move it was asked on
+	if turn.is_changed() && prompt.0.is_some() {
+		prompt.0 = None;
+	}
+
+	// Ticking alone doesn't need the prompt shown again
+	let expired = prompt.bypass_change_detection().0.as_mut().is_some_and(|timer| timer.tick(time.delta()).finished());
+	if expired {
+		prompt.0 = None;
+	}
+
+	if !keys.just_pressed(KeyCode::R) || players.get(turn.0) != Player::Human || outcome.0.is_some() {
+		return;
+	}
+
+	if prompt.0.take().is_some() {
+		game_over_event.send(GameOverEvent(GameResult::Resignation {
+			winner: turn.0.opposite()
+		}));
+	} else {
+		prompt.0 = Some(Timer::from_seconds(3., TimerMode::Once));
+	}
+}
+
+// Ctrl+Z asks to take back the last move, and Ctrl+Y to play a move taken back again
+pub struct TakebackEvent;
+
+pub struct RedoEvent;
+
+fn takeback_keys(keys: Res<Input<KeyCode>>, mut takeback_event: EventWriter<TakebackEvent>, mut redo_event: EventWriter<RedoEvent>) {
+	if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+		return;
+	}
+
+	if keys.just_pressed(KeyCode::Z) {
+		takeback_event.send(TakebackEvent);
+	}
+	if keys.just_pressed(KeyCode::Y) {
+		redo_event.send(RedoEvent);
+	}
+}
+
+// Takebacks are a request to the opponent, which the computer and engines always agree to.
+// There's nobody to ask without a human playing
+fn takebacks_allowed(players: &Players, pending_promotion: &PendingPromotion) -> bool {
+	players.has(Player::Human) && pending_promotion.0.is_none()
+}
+
+// Against the computer its reply goes too, so it's the human's turn again. Taking back
+// the move that ended the game picks it up again. The clocks go back to when the first move
+// taken back was played
+pub fn take_back(mut commands: Commands, mut event_reader: EventReader<TakebackEvent>, players: Res<Players>, pending_promotion: Res<PendingPromotion>, mut ai_task: ResMut<AiTask>, mut clock: ResMut<GameClock>, mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, mut turn: ResMut<PlayerTurn>, mut outcome: ResMut<GameOutcome>, mut next_state: ResMut<NextState<AppState>>, mut reset_selected_event: EventWriter<ResetSelectedEvent>, piece_assets: Res<PieceAssets>, mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>) {
+	for _event in event_reader.iter() {
+		if !takebacks_allowed(&players, &pending_promotion) {
+			continue;
+		}
+
+		let mut taken_back = Vec::new();
+		while let Some(undone) = history.take_back(&mut position.0) {
+			taken_back.push(undone);
+			if players.get(position.0.side_to_move()) == Player::Human {
+				break;
+			}
+		}
+
+		match taken_back[..] {
+			[] => continue,
+			[(mv, undo)] => unmove_piece_entities(&mut commands, &piece_assets, &position.0, mv, undo.captured(), &mut pieces_query),
+			_ => respawn_pieces(&mut commands, &piece_assets, &position.0, &pieces_query)
+		}
+
+		if let Some((_, taken_back_clock)) = history.redo.last() {
+			*clock = taken_back_clock.clone();
+		}
+
+		// A reply the computer is still thinking about is for a position that's gone
+		ai_task.cancel();
+		turn.0 = position.0.side_to_move();
+		reset_selected_event.send(ResetSelectedEvent);
+		if outcome.0.take().is_some() {
+			next_state.set(AppState::InGame);
+		}
+	}
+}
+
+// Play the moves taken back again, up to the human's next turn or the end of the game, with the
+// clocks as they were when they were first played
+pub fn redo(mut commands: Commands, mut event_reader: EventReader<RedoEvent>, players: Res<Players>, pending_promotion: Res<PendingPromotion>, mut ai_task: ResMut<AiTask>, mut clock: ResMut<GameClock>, outcome: Res<GameOutcome>, mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, mut turn: ResMut<PlayerTurn>, mut reset_selected_event: EventWriter<ResetSelectedEvent>, mut game_over_event: EventWriter<GameOverEvent>, piece_assets: Res<PieceAssets>, mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>) {
+	for _event in event_reader.iter() {
+		if outcome.0.is_some() || !takebacks_allowed(&players, &pending_promotion) {
+			continue;
+		}
+
+		let start = position.0.clone();
+		let mut redone = Vec::new();
+		let mut result = None;
+		while let Some((mv, redo_clock)) = history.redo.last().cloned() {
+			history.play(&mut position.0, mv, &redo_clock);
+			redone.push(mv);
+
+			result = position.0.game_result_with_history(&history.hashes);
+			if result.is_some() || players.get(position.0.side_to_move()) == Player::Human {
+				break;
+			}
+		}
+
+		match redone[..] {
+			[] => continue,
+			[mv] => play_move_entities(&mut commands, &piece_assets, &start, mv, &mut pieces_query),
+			_ => respawn_pieces(&mut commands, &piece_assets, &position.0, &pieces_query)
+		}
+
+		// The last move is pressed for as usual
+		if let Some(redone_clock) = history.clocks.last() {
+			*clock = redone_clock.clone();
+		}
+
+		ai_task.cancel();
+		turn.0 = position.0.side_to_move();
+		reset_selected_event.send(ResetSelectedEvent);
+		if let Some(result) = result {
+			game_over_event.send(GameOverEvent(result));
+		}
+	}
+}
+
+// Start over from the position the game started from. The pieces are spawned again by `create_pieces`
+pub fn reset_game(mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, mut turn: ResMut<PlayerTurn>, mut outcome: ResMut<GameOutcome>, mut pending_promotion: ResMut<PendingPromotion>) {
+	position.0 = history.start.clone();
+	history.clear();
+	turn.0 = position.0.side_to_move();
+	outcome.0 = None;
+	pending_promotion.0 = None;
+}
+
+fn clear_selection(mut selected_square: ResMut<SelectedSquare>, mut selected_piece: ResMut<SelectedPiece>) {
+	selected_square.entity = None;
+	selected_piece.entity = None;
+}
+
 #[derive(Resource)]
 struct SquareMaterials {
 	highlight_color: Handle<StandardMaterial>,
 	selected_color: Handle<StandardMaterial>,
+	move_color: Handle<StandardMaterial>,
+	capture_color: Handle<StandardMaterial>,
+	illegal_color: Handle<StandardMaterial>,
+	check_color: Handle<StandardMaterial>,
+	checker_color: Handle<StandardMaterial>,
+	last_move_white_color: Handle<StandardMaterial>,
+	last_move_black_color: Handle<StandardMaterial>,
 	black_color: Handle<StandardMaterial>,
 	white_color: Handle<StandardMaterial>
 }
@@ -253,6 +748,17 @@ impl FromWorld for SquareMaterials {
 		SquareMaterials {
 			highlight_color: materials.add(Color::rgb(0.8, 0.3, 0.3).into()),
 			selected_color: materials.add(Color::rgb(0.9, 0.1, 0.1).into()),
+			move_color: materials.add(Color::rgb(0.4, 0.7, 0.4).into()),
+			capture_color: materials.add(Color::rgb(0.9, 0.6, 0.2).into()),
+			illegal_color: materials.add(Color::rgb(0.5, 0., 0.).into()),
+			check_color: materials.add(StandardMaterial {
+				base_color: Color::rgb(1., 0.1, 0.1),
+				emissive: Color::rgb(0.6, 0., 0.),
+				..default()
+			}),
+			checker_color: materials.add(Color::rgb(0.7, 0.3, 0.6).into()),
+			last_move_white_color: materials.add(Color::rgb(0.9, 0.9, 0.5).into()),
+			last_move_black_color: materials.add(Color::rgb(0.4, 0.4, 0.1).into()),
 			black_color: materials.add(Color::rgb(0., 0.1, 0.1).into()),
 			white_color: materials.add(Color::rgb(1., 0.9, 0.9).into())
 		}
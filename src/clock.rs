@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use chess_core::{ChessClock, PieceColor, SearchLimits, TimeControl};
+use std::time::Duration;
+use crate::{board::*, history::*, menu::*, replay::*};
+
+// The time control given with `--time-control`, games are untimed without one
+#[derive(Default, Resource)]
+pub struct ClockSettings(pub Option<TimeControl>);
+
+#[derive(Clone, Default, Resource)]
+pub struct GameClock {
+	clock: Option<ChessClock>,
+	// Time the side to move has spent on its move so far
+	spent: Duration,
+	// Moves the clock was pressed for, to catch up with the history
+	pressed: usize
+}
+
+impl GameClock {
+	pub fn is_timed(&self) -> bool {
+		self.clock.is_some()
+	}
+
+	pub fn time_left(&self, color: PieceColor, turn: &PlayerTurn) -> Option<Duration> {
+		let spent = if color == turn.0 { self.spent } else { Duration::ZERO };
+		self.clock.as_ref().map(|clock| clock.time_left(color, spent))
+	}
+
+	// How long the computer or engine playing the side to move should think, when the game is timed
+	pub fn search_limits(&self, turn: &PlayerTurn) -> Option<SearchLimits> {
+		self.clock.as_ref().map(|clock| clock.search_limits(turn.0, self.spent))
+	}
+}
+
+fn reset_clock(settings: Res<ClockSettings>, mut clock: ResMut<GameClock>) {
+	*clock = GameClock {
+		clock: settings.0.clone().map(ChessClock::new),
+		..default()
+	};
+}
+
+// Hand the clock over to the other side after every move
+fn press_clock(turn: Res<PlayerTurn>, history: Res<MoveHistory>, mut game_clock: ResMut<GameClock>) {
+	if game_clock.pressed >= history.moves.len() {
+		return;
+	}
+
+	let spent = game_clock.spent;
+	if let Some(clock) = &mut game_clock.clock {
+		clock.press(turn.0.opposite(), spent);
+	}
+	game_clock.spent = Duration::ZERO;
+	game_clock.pressed = history.moves.len();
+}
+
+// Run down the clock of the side to move, which loses once it's out of time
+fn run_clock(time: Res<Time>, turn: Res<PlayerTurn>, position: Res<GamePosition>, outcome: Res<GameOutcome>, mut game_clock: ResMut<GameClock>, mut game_over_event: EventWriter<GameOverEvent>) {
+	if outcome.0.is_some() {
+		return;
+	}
+
+	game_clock.spent += time.delta();
+
+	let flagged = match &game_clock.clock {
+		Some(clock) => clock.is_flagged(turn.0, game_clock.spent),
+		None => false
+	};
+	if flagged {
+		game_over_event.send(GameOverEvent(position.0.timeout_result(turn.0)));
+	}
+}
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<ClockSettings>()
+			.init_resource::<GameClock>()
+			.add_system(reset_clock.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(press_clock.after(make_move).after(promote_pawn).after(take_back).after(redo).run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(run_clock.after(press_clock).before(game_over).run_if(not_replaying).run_if(in_state(AppState::InGame)));
+	}
+}
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use chess_core::{evaluate, DrawReason, GameResult, PieceColor};
+use crate::{board::*, history::*, menu::*, replay::*};
+
+// The color whose draw offer is waiting on an answer. It lapses once the other side moves instead
+#[derive(Default, Resource)]
+pub struct DrawOffer(pub Option<PieceColor>);
+
+// A player offers a draw, or agrees to the one the other side offered
+pub struct DrawOfferEvent(pub PieceColor);
+
+// The side to move claims a draw by threefold repetition or the fifty-move rule
+pub struct ClaimDrawEvent;
+
+// O offers a draw or accepts the one on the table, D claims one. Only for the human to move
+fn draw_keys(keys: Res<Input<KeyCode>>, turn: Res<PlayerTurn>, players: Res<Players>, outcome: Res<GameOutcome>, mut offer_event: EventWriter<DrawOfferEvent>, mut claim_event: EventWriter<ClaimDrawEvent>) {
+	if players.get(turn.0) != Player::Human || outcome.0.is_some() {
+		return;
+	}
+
+	if keys.just_pressed(KeyCode::O) {
+		offer_event.send(DrawOfferEvent(turn.0));
+	}
+	if keys.just_pressed(KeyCode::D) {
+		claim_event.send(ClaimDrawEvent);
+	}
+}
+
+// An offer from each side makes a draw
+fn handle_draw_offers(mut event_reader: EventReader<DrawOfferEvent>, mut offer: ResMut<DrawOffer>, outcome: Res<GameOutcome>, mut game_over_event: EventWriter<GameOverEvent>) {
+	for DrawOfferEvent(color) in event_reader.iter() {
+		if outcome.0.is_some() {
+			continue;
+		}
+
+		if offer.0 == Some(color.opposite()) {
+			game_over_event.send(GameOverEvent(GameResult::Draw(DrawReason::Agreement)));
+			offer.0 = None;
+		} else if offer.0.is_none() {
+			info!("{:?} offers a draw", color);
+			offer.0 = Some(*color);
+		}
+	}
+}
+
+// Claims only count when the rules allow them, otherwise the game goes on
+fn handle_draw_claims(mut event_reader: EventReader<ClaimDrawEvent>, position: Res<GamePosition>, history: Res<MoveHistory>, outcome: Res<GameOutcome>, mut game_over_event: EventWriter<GameOverEvent>) {
+	for _event in event_reader.iter() {
+		if outcome.0.is_some() {
+			continue;
+		}
+
+		match position.0.claimable_draw(&history.hashes) {
+			Some(reason) => game_over_event.send(GameOverEvent(GameResult::Draw(reason))),
+			None => info!("There's no draw to claim")
+		}
+	}
+}
+
+// Moving instead of answering turns the offer down
+fn expire_draw_offer(turn: Res<PlayerTurn>, mut offer: ResMut<DrawOffer>) {
+	if turn.is_changed() && offer.0 == Some(turn.0) {
+		offer.0 = None;
+	}
+}
+
+fn clear_draw_offer(mut offer: ResMut<DrawOffer>) {
+	offer.0 = None;
+}
+
+// The built-in computer takes a draw, offered or claimable, whenever it isn't ahead
+fn computer_draws(turn: Res<PlayerTurn>, players: Res<Players>, position: Res<GamePosition>, history: Res<MoveHistory>, offer: Res<DrawOffer>, outcome: Res<GameOutcome>, mut offer_event: EventWriter<DrawOfferEvent>, mut claim_event: EventWriter<ClaimDrawEvent>) {
+	if !(turn.is_changed() || offer.is_changed()) || players.get(turn.0) != Player::Computer || outcome.0.is_some() {
+		return;
+	}
+
+	// The evaluation is from the side to move's point of view
+	if evaluate(&position.0) > 0 {
+		return;
+	}
+
+	if position.0.claimable_draw(&history.hashes).is_some() {
+		claim_event.send(ClaimDrawEvent);
+	} else if offer.0 == Some(turn.0.opposite()) {
+		offer_event.send(DrawOfferEvent(turn.0));
+	}
+}
+
+pub struct DrawPlugin;
+
+impl Plugin for DrawPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<DrawOffer>()
+			.add_event::<DrawOfferEvent>()
+			.add_event::<ClaimDrawEvent>()
+			.add_system(draw_keys.run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(computer_draws.run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(expire_draw_offer)
+			.add_system(clear_draw_offer.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(handle_draw_offers.after(draw_keys).after(computer_draws).before(game_over).run_if(in_state(AppState::InGame)))
+			.add_system(handle_draw_claims.after(draw_keys).after(computer_draws).before(game_over).run_if(in_state(AppState::InGame)));
+	}
+}
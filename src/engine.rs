@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use chess_core::{parse_xboard_move, EngineError, GameResult, Move, PieceColor, Position, SearchLimits, UciEngine, UciInfo, UciMessage, UciScore, XBoardEngine, XBoardMessage, XBoardThinking};
+use std::time::Duration;
+use crate::{ai::*, board::*, clock::*, draw::*, history::*, menu::*, replay::*};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum EngineProtocol {
+	#[default]
+	Uci,
+	XBoard
+}
+
+// The engine binary given with `--engine <path>`, and the protocol it speaks
+#[derive(Default, Resource)]
+pub struct EngineSettings {
+	pub path: Option<String>,
+	pub protocol: EngineProtocol
+}
+
+// Either protocol, behind the few things the game needs from an engine
+enum EngineClient {
+	Uci(UciEngine),
+	XBoard(XBoardEngine)
+}
+
+// What the game cares about in the engine's messages
+enum EngineEvent {
+	Analysis(String),
+	BestMove(Option<Move>),
+	// A move the engine sent that couldn't be read or isn't legal, as it sent it
+	IllegalMove(String),
+	OfferDraw,
+	Resign
+}
+
+impl EngineClient {
+	fn start(path: &str, protocol: EngineProtocol) -> Result<EngineClient, EngineError> {
+		let timeout = Duration::from_secs(5);
+
+		match protocol {
+			EngineProtocol::Uci => {
+				let mut engine = UciEngine::spawn(path)?;
+				engine.init(timeout)?;
+				Ok(EngineClient::Uci(engine))
+			},
+			EngineProtocol::XBoard => {
+				let mut engine = XBoardEngine::spawn(path)?;
+				engine.init(timeout)?;
+				Ok(EngineClient::XBoard(engine))
+			}
+		}
+	}
+
+	fn name(&self) -> Option<&str> {
+		match self {
+			EngineClient::Uci(engine) => engine.name(),
+			EngineClient::XBoard(engine) => engine.name()
+		}
+	}
+
+	// Think about the position after `moves`, without limits until stopped
+	fn think(&mut self, start: &Position, moves: &[Move], limits: &SearchLimits) -> Result<(), EngineError> {
+		match self {
+			EngineClient::Uci(engine) => {
+				engine.set_position(start, moves)?;
+				engine.go(limits)
+			},
+			EngineClient::XBoard(engine) => {
+				engine.set_position(start, moves)?;
+				engine.go(limits)
+			}
+		}
+	}
+
+	// Returns whether a best move is still to come
+	fn stop(&mut self) -> Result<bool, EngineError> {
+		match self {
+			EngineClient::Uci(engine) => engine.stop().map(|_| true),
+			EngineClient::XBoard(engine) => engine.stop()
+		}
+	}
+
+	// Only CECP has draw offers, UCI engines just play on
+	fn offer_draw(&mut self) -> Result<(), EngineError> {
+		match self {
+			EngineClient::Uci(_) => Ok(()),
+			EngineClient::XBoard(engine) => engine.send("draw")
+		}
+	}
+
+	// The next message worth looking at, `position` being the one the engine is thinking about
+	fn poll(&mut self, position: &Position) -> Result<Option<EngineEvent>, EngineError> {
+		loop {
+			let event = match self {
+				EngineClient::Uci(engine) => match engine.try_recv()? {
+					None => return Ok(None),
+					Some(UciMessage::Info(info)) => describe_uci_info(position, &info).map(EngineEvent::Analysis),
+					Some(UciMessage::BestMove { best_move, .. }) => Some(EngineEvent::BestMove(best_move)),
+					Some(_) => None
+				},
+				EngineClient::XBoard(engine) => match engine.try_recv()? {
+					None => return Ok(None),
+					Some(XBoardMessage::Thinking(thinking)) => Some(EngineEvent::Analysis(describe_xboard_thinking(position, &thinking))),
+					Some(XBoardMessage::Move(text)) => {
+						// Keep the engine from playing on by itself
+						engine.send("force")?;
+						match parse_xboard_move(position, &text) {
+							Some(mv) => Some(EngineEvent::BestMove(Some(mv))),
+							None => Some(EngineEvent::IllegalMove(text))
+						}
+					},
+					Some(XBoardMessage::Resign) => Some(EngineEvent::Resign),
+					Some(XBoardMessage::OfferDraw) => Some(EngineEvent::OfferDraw),
+					Some(_) => None
+				}
+			};
+
+			if let Some(event) = event {
+				return Ok(Some(event));
+			}
+		}
+	}
+}
+
+// The running engine. It isn't Sync, so it's kept as a non-send resource
+pub struct Engine {
+	client: EngineClient,
+	state: EngineState,
+	// Searches that were stopped, whose best moves are still to come and get ignored
+	stopped: usize,
+	// Stopped by pausing the game, to pick up again when it resumes
+	paused: bool
+}
+
+impl Engine {
+	fn stop(&mut self) {
+		if self.state != EngineState::Idle {
+			match self.client.stop() {
+				Ok(true) => self.stopped += 1,
+				Ok(false) => {},
+				Err(error) => error!("Couldn't stop the engine: {}", error)
+			}
+		}
+
+		self.state = EngineState::Idle;
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EngineState {
+	Idle,
+	// Thinking about its own move
+	Playing,
+	// Looking at the position while someone else is to move
+	Analysing
+}
+
+// The engine's latest take on the position, as text for the UI
+#[derive(Default, Resource)]
+pub struct EngineAnalysis(pub Option<String>);
+
+// Start the engine if there is one. Colors meant for it go to the built-in computer if it doesn't work
+fn start_engine(world: &mut World) {
+	let settings = world.resource::<EngineSettings>();
+	let (path, protocol) = (settings.path.clone(), settings.protocol);
+
+	let client = match &path {
+		Some(path) => EngineClient::start(path, protocol).map_err(|error| format!("Couldn't start the engine {}: {}", path, error)),
+		None => Err("No engine was given with --engine".to_string())
+	};
+
+	match client {
+		Ok(client) => {
+			info!("Started {}", client.name().or(path.as_deref()).unwrap_or("the engine"));
+			world.insert_non_send_resource(Engine {
+				client,
+				state: EngineState::Idle,
+				stopped: 0,
+				paused: false
+			});
+		},
+		Err(error) => {
+			let mut players = world.resource_mut::<Players>();
+			if !players.has(Player::Engine) && path.is_none() {
+				return;
+			}
+
+			error!("{}", error);
+			for color in [PieceColor::White, PieceColor::Black] {
+				if players.get(color) == Player::Engine {
+					players.set(color, Player::Computer);
+				}
+			}
+		}
+	}
+}
+
+// Let the engine play its turns, and analyse the others
+fn engine_think(turn: Res<PlayerTurn>, players: Res<Players>, outcome: Res<GameOutcome>, history: Res<MoveHistory>, settings: Res<AiSettings>, clock: Res<GameClock>, mut engine: NonSendMut<Engine>, mut analysis: ResMut<EngineAnalysis>) {
+	if !turn.is_changed() {
+		return;
+	}
+
+	engine.stop();
+	analysis.0 = None;
+	think(&turn, &players, &outcome, &history, &settings, &clock, &mut engine);
+}
+
+fn think(turn: &PlayerTurn, players: &Players, outcome: &GameOutcome, history: &MoveHistory, settings: &AiSettings, clock: &GameClock, engine: &mut Engine) {
+	if outcome.0.is_some() {
+		return;
+	}
+
+	let (state, limits) = if players.get(turn.0) == Player::Engine {
+		(EngineState::Playing, clock.search_limits(turn).unwrap_or(SearchLimits::time(settings.think_time)))
+	} else if !players.has(Player::Engine) {
+		// An engine that isn't playing either side looks at every position for as long as it's on the board
+		(EngineState::Analysing, SearchLimits::default())
+	} else {
+		return;
+	};
+
+	match engine.client.think(&history.start, &history.moves, &limits) {
+		Ok(()) => engine.state = state,
+		Err(error) => error!("Couldn't start the engine: {}", error)
+	}
+}
+
+// Nothing to think about once the game is paused, over or left
+fn stop_engine(state: Res<State<AppState>>, mut engine: NonSendMut<Engine>, mut analysis: ResMut<EngineAnalysis>) {
+	// The state is already the one being entered
+	engine.paused = state.0 == AppState::Paused && engine.state != EngineState::Idle;
+	engine.stop();
+	analysis.0 = None;
+}
+
+// The turn is the same after a pause, so the engine is started again here
+fn resume_engine(turn: Res<PlayerTurn>, players: Res<Players>, outcome: Res<GameOutcome>, history: Res<MoveHistory>, settings: Res<AiSettings>, clock: Res<GameClock>, mut engine: NonSendMut<Engine>) {
+	if engine.paused {
+		engine.paused = false;
+		think(&turn, &players, &outcome, &history, &settings, &clock, &mut engine);
+	}
+}
+
+// Read what the engine sent, playing its best move when it's its turn
+fn engine_messages(position: Res<GamePosition>, players: Res<Players>, mut engine: NonSendMut<Engine>, mut analysis: ResMut<EngineAnalysis>, mut move_event: EventWriter<MoveEvent>, mut offer_event: EventWriter<DrawOfferEvent>, mut game_over_event: EventWriter<GameOverEvent>) {
+	loop {
+		let event = match engine.client.poll(&position.0) {
+			Ok(Some(event)) => event,
+			Ok(None) => break,
+			Err(error) => {
+				if engine.state != EngineState::Idle {
+					error!("Lost the engine: {}", error);
+					engine.state = EngineState::Idle;
+				}
+				break;
+			}
+		};
+
+		match event {
+			EngineEvent::Analysis(text) => {
+				if engine.stopped == 0 && engine.state != EngineState::Idle {
+					analysis.0 = Some(text);
+				}
+			},
+			EngineEvent::BestMove(_) | EngineEvent::IllegalMove(_) if engine.stopped > 0 => {
+				engine.stopped -= 1;
+			},
+			EngineEvent::BestMove(best_move) => {
+				if engine.state == EngineState::Playing {
+					engine.state = EngineState::Idle;
+
+					// The engine's move is checked like any other, an illegal one just leaves the game waiting
+					match best_move {
+						Some(mv) if position.0.is_legal(mv) => move_event.send(MoveEvent(mv)),
+						Some(mv) => warn!("The engine played an illegal move: {}", mv),
+						None => warn!("The engine didn't find a move")
+					}
+				}
+			},
+			EngineEvent::IllegalMove(text) => {
+				if engine.state == EngineState::Playing {
+					engine.state = EngineState::Idle;
+					warn!("The engine played an illegal move: {}", text);
+				}
+			},
+			EngineEvent::OfferDraw => {
+				if let Some(color) = engine_color(&players) {
+					offer_event.send(DrawOfferEvent(color));
+				}
+			},
+			EngineEvent::Resign => {
+				if let Some(color) = engine_color(&players) {
+					game_over_event.send(GameOverEvent(GameResult::Resignation {
+						winner: color.opposite()
+					}));
+				}
+			}
+		}
+	}
+}
+
+// Draw offers from the engine's opponent are passed on, the engine answers with an offer of its own
+fn engine_draw_offers(mut event_reader: EventReader<DrawOfferEvent>, players: Res<Players>, mut engine: NonSendMut<Engine>) {
+	for DrawOfferEvent(color) in event_reader.iter() {
+		if engine_color(&players) == Some(color.opposite()) {
+			if let Err(error) = engine.client.offer_draw() {
+				error!("Couldn't offer the engine a draw: {}", error);
+			}
+		}
+	}
+}
+
+// The color the engine plays, if it plays just the one
+fn engine_color(players: &Players) -> Option<PieceColor> {
+	match (players.white, players.black) {
+		(Player::Engine, Player::Engine) => None,
+		(Player::Engine, _) => Some(PieceColor::White),
+		(_, Player::Engine) => Some(PieceColor::Black),
+		_ => None
+	}
+}
+
+// e.g. "+0.35 (depth 18) e4 e5 Nf3", with the score from white's side
+fn describe_uci_info(position: &Position, info: &UciInfo) -> Option<String> {
+	let (score, depth) = (info.score?, info.depth?);
+	let sign = match position.side_to_move() {
+		PieceColor::White => 1,
+		PieceColor::Black => -1
+	};
+
+	let mut text = match score {
+		UciScore::Centipawns(centipawns) => format!("{:+.2}", (sign * centipawns) as f32 / 100.),
+		UciScore::Mate(moves) => format!("#{}", sign * moves)
+	};
+	text.push_str(&format!(" (depth {})", depth));
+
+	// Engines don't always send sound lines, stop at the first move that doesn't fit
+	let mut line_position = position.clone();
+	for mv in info.pv.iter().copied().take(8) {
+		if !line_position.is_legal(mv) {
+			break;
+		}
+		text.push_str(&format!(" {}", line_position.san(mv)));
+		line_position.make_move(mv);
+	}
+
+	Some(text)
+}
+
+// Same as for UCI, with the line left as the engine wrote it
+fn describe_xboard_thinking(position: &Position, thinking: &XBoardThinking) -> String {
+	let sign = match position.side_to_move() {
+		PieceColor::White => 1,
+		PieceColor::Black => -1
+	};
+
+	let score = match thinking.mate_in() {
+		Some(moves) => format!("#{}", sign * moves),
+		None => format!("{:+.2}", (sign * thinking.score) as f32 / 100.)
+	};
+	let pv = thinking.pv.split_whitespace().take(8).collect::<Vec<&str>>().join(" ");
+
+	format!("{} (depth {}) {}", score, thinking.depth, pv)
+}
+
+fn engine_running(engine: Option<NonSend<Engine>>) -> bool {
+	engine.is_some()
+}
+
+pub struct EnginePlugin;
+
+impl Plugin for EnginePlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<EngineSettings>()
+			.init_resource::<EngineAnalysis>()
+			.add_startup_system(start_engine)
+			.add_system(stop_engine.run_if(engine_running).in_schedule(OnExit(AppState::InGame)))
+			.add_system(resume_engine.run_if(engine_running).run_if(not_replaying).in_schedule(OnEnter(AppState::InGame)))
+			.add_system(engine_think.after(game_over).after(press_clock).run_if(engine_running).run_if(not_replaying).run_if(in_state(AppState::InGame)))
+			.add_system(engine_messages.run_if(engine_running).run_if(not_browsing).after(engine_think).run_if(in_state(AppState::InGame)))
+			.add_system(engine_draw_offers.run_if(engine_running));
+	}
+}
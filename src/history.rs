@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use chess_core::{Move, PgnGame, PgnResult, PieceColor, Position, Undo};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{board::*, clock::*, menu::*, pieces::*, replay::*};
+
+// Every move played since the starting position, along with its SAN, the Zobrist hash
+// of the position it was played from, for repetitions, and what it takes to take it back,
+// the clocks included
+#[derive(Resource)]
+pub struct MoveHistory {
+	pub start: Position,
+	pub moves: Vec<Move>,
+	pub sans: Vec<String>,
+	pub hashes: Vec<u64>,
+	pub undos: Vec<Undo>,
+	// The clocks as they were when each move was played, before they were pressed for it
+	pub clocks: Vec<GameClock>,
+	// Moves taken back that can be played again with their clocks, the next one last
+	pub redo: Vec<(Move, GameClock)>
+}
+
+impl FromWorld for MoveHistory {
+	fn from_world(world: &mut World) -> Self {
+		MoveHistory {
+			start: world.resource::<GamePosition>().0.clone(),
+			moves: Vec::new(),
+			sans: Vec::new(),
+			hashes: Vec::new(),
+			undos: Vec::new(),
+			clocks: Vec::new(),
+			redo: Vec::new()
+		}
+	}
+}
+
+impl MoveHistory {
+	// Play a move on `position` with the clocks at `clock`, recording and logging it
+	pub fn play(&mut self, position: &mut Position, mv: Move, clock: &GameClock) {
+		let san = position.san(mv);
+		match position.side_to_move() {
+			PieceColor::White => info!("{}. {}", position.fullmove_number(), san),
+			PieceColor::Black => info!("{}... {}", position.fullmove_number(), san)
+		}
+
+		self.moves.push(mv);
+		self.sans.push(san);
+		self.hashes.push(position.zobrist_hash());
+		self.undos.push(position.make_move(mv));
+
+		self.clocks.push(clock.clone());
+
+		// Playing the move that was taken back keeps the ones after it to redo
+		if self.redo.last().is_some_and(|(redo_mv, _)| *redo_mv == mv) {
+			self.redo.pop();
+		} else {
+			self.redo.clear();
+		}
+	}
+
+	// Take the last move back on `position`, keeping it to redo
+	pub fn take_back(&mut self, position: &mut Position) -> Option<(Move, Undo)> {
+		let mv = self.moves.pop()?;
+		let undo = self.undos.pop()?;
+		let clock = self.clocks.pop()?;
+		self.sans.pop();
+		self.hashes.pop();
+		self.redo.push((mv, clock));
+
+		position.unmake_move(mv, undo);
+		Some((mv, undo))
+	}
+
+	pub fn clear(&mut self) {
+		self.moves.clear();
+		self.sans.clear();
+		self.hashes.clear();
+		self.undos.clear();
+		self.clocks.clear();
+		self.redo.clear();
+	}
+
+	pub fn to_pgn(&self, result: PgnResult) -> PgnGame {
+		let mut game = PgnGame::new(self.start.clone(), self.moves.clone(), result);
+		game.set_tag("Event", "Casual game");
+		game.set_tag("Site", "Bevy Chess");
+		game.set_tag("Date", &today());
+
+		game
+	}
+}
+
+// Today's date as YYYY.MM.DD, from the days since the Unix epoch
+fn today() -> String {
+	let days = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_secs() / 86400)
+		.unwrap_or(0) as i64;
+
+	// Converts days to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z - era * 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn write_pgn(game: &PgnGame) {
+	let seconds = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_secs())
+		.unwrap_or(0);
+	let path = format!("game-{}.pgn", seconds);
+
+	match std::fs::write(&path, game.to_pgn()) {
+		Ok(()) => info!("Saved the game to {}", path),
+		Err(error) => error!("Couldn't save the game to {}: {}", path, error)
+	}
+}
+
+// Save the game so far, from the P key or the game over screen
+pub struct ExportPgnEvent;
+
+fn export_pgn_on_key(keys: Res<Input<KeyCode>>, mut export_event: EventWriter<ExportPgnEvent>) {
+	if keys.just_pressed(KeyCode::P) {
+		export_event.send(ExportPgnEvent);
+	}
+}
+
+fn export_pgn(mut event_reader: EventReader<ExportPgnEvent>, history: Res<MoveHistory>, outcome: Res<GameOutcome>) {
+	for _event in event_reader.iter() {
+		let result = outcome.0.map(PgnResult::from).unwrap_or(PgnResult::Unknown);
+		write_pgn(&history.to_pgn(result));
+	}
+}
+
+// Save the game once it's over, going by the outcome rather than the events, which can come in
+// after the game has already ended
+fn export_pgn_on_game_over(history: Res<MoveHistory>, outcome: Res<GameOutcome>) {
+	if !outcome.is_changed() {
+		return;
+	}
+
+	if let Some(result) = outcome.0 {
+		write_pgn(&history.to_pgn(PgnResult::from(result)));
+	}
+}
+
+// The board as it was a number of plies into the game, shown while looking back through it.
+// Nothing can be played while browsing, the game carries on in `GamePosition` afterwards
+#[derive(Default, Resource)]
+pub struct Browsing(pub Option<(usize, Position)>);
+
+impl Browsing {
+	// The ply on the board, the last one when not browsing
+	pub fn ply(&self, history: &MoveHistory) -> usize {
+		self.0.as_ref().map_or(history.moves.len(), |(ply, _)| *ply)
+	}
+
+	pub fn position<'a>(&'a self, position: &'a GamePosition) -> &'a Position {
+		self.0.as_ref().map_or(&position.0, |(_, shown)| shown)
+	}
+}
+
+// Run condition for what plays moves on the board, which waits while looking back through the game
+pub fn not_browsing(browsing: Res<Browsing>) -> bool {
+	browsing.0.is_none()
+}
+
+// Show the board after this many plies, going back to the game at the last one
+pub struct BrowseEvent(pub usize);
+
+// Look back through the game with the left and right arrow keys, which step through replays instead
+fn browse_keys(keys: Res<Input<KeyCode>>, browsing: Res<Browsing>, history: Res<MoveHistory>, mut browse_event: EventWriter<BrowseEvent>) {
+	let ply = browsing.ply(&history);
+	if keys.just_pressed(KeyCode::Left) && ply > 0 {
+		browse_event.send(BrowseEvent(ply - 1));
+	} else if keys.just_pressed(KeyCode::Right) && ply < history.moves.len() {
+		browse_event.send(BrowseEvent(ply + 1));
+	}
+}
+
+fn browse(mut commands: Commands, mut event_reader: EventReader<BrowseEvent>, position: Res<GamePosition>, history: Res<MoveHistory>, pending_promotion: Res<PendingPromotion>, mut browsing: ResMut<Browsing>, mut reset_selected_event: EventWriter<ResetSelectedEvent>, piece_assets: Res<PieceAssets>, pieces_query: Query<(Entity, &mut Piece), Without<Taken>>) {
+	let ply = match event_reader.iter().last() {
+		Some(BrowseEvent(ply)) => (*ply).min(history.moves.len()),
+		None => return
+	};
+	// The pawn waiting to be promoted has to stay on the board
+	if ply == browsing.ply(&history) || pending_promotion.0.is_some() {
+		return;
+	}
+
+	if ply == history.moves.len() {
+		respawn_pieces(&mut commands, &piece_assets, &position.0, &pieces_query);
+		browsing.0 = None;
+	} else {
+		let mut shown = history.start.clone();
+		for mv in &history.moves[..ply] {
+			shown.make_move(*mv);
+		}
+		respawn_pieces(&mut commands, &piece_assets, &shown, &pieces_query);
+		browsing.0 = Some((ply, shown));
+	}
+
+	// The selected piece is gone with the rest
+	reset_selected_event.send(ResetSelectedEvent);
+}
+
+// A new game starts out on its own board
+fn stop_browsing(mut browsing: ResMut<Browsing>) {
+	browsing.0 = None;
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<MoveHistory>()
+			.init_resource::<Browsing>()
+			.add_event::<ExportPgnEvent>()
+			.add_event::<BrowseEvent>()
+			.add_system(stop_browsing.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(browse_keys.run_if(not_replaying).run_if(playing_or_over))
+			.add_system(browse.after(browse_keys).run_if(playing_or_over))
+			.add_system(export_pgn_on_key.run_if(game_on_board))
+			.add_system(export_pgn.after(export_pgn_on_key))
+			.add_system(export_pgn_on_game_over.after(game_over));
+	}
+}
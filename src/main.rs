@@ -1,17 +1,47 @@
 mod pieces;
 mod board;
+mod history;
+mod replay;
 mod ui;
+mod ai;
+mod engine;
+mod draw;
+mod menu;
+mod clock;
 
 use pieces::*;
 use board::*;
+use history::*;
+use replay::*;
 use ui::*;
+use ai::*;
+use engine::*;
+use draw::*;
+use menu::*;
+use clock::*;
 
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
+use chess_core::{Position, TimeControl};
 
 fn main() {
-	App::new()
-		.insert_resource(Msaa::Sample4)
+	let replay = replay_from_args();
+	// Games set up on the command line start right away, without the main menu
+	let skip_menu = replay.is_some() || ["--fen", "--ai", "--engine-plays"].iter().any(|arg| arg_value(arg).is_some());
+	let start = start_position();
+	let position = match &replay {
+		Some(replay) => replay.game().start.clone(),
+		None => start.clone()
+	};
+
+	let mut app = App::new();
+	app.insert_resource(Msaa::Sample4)
+		.insert_resource(GamePosition(position))
+		.insert_resource(StartPosition(start))
+		.insert_resource(players_from_args())
+		.insert_resource(ai_settings_from_args())
+		.insert_resource(engine_settings_from_args())
+		.insert_resource(clock_settings_from_args())
 		.add_plugins(DefaultPlugins.set(WindowPlugin {
 			primary_window: Some(Window {
 				title: "Chess!".into(),
@@ -24,11 +54,125 @@ fn main() {
 			.build()
 			.disable::<DebugPickingPlugin>()
 		)
+		.add_state::<AppState>()
+		.add_plugin(MenuPlugin)
 		.add_plugin(BoardPlugin)
 		.add_plugin(PiecesPlugin)
+		.add_plugin(HistoryPlugin)
+		.add_plugin(ReplayPlugin)
+		.add_plugin(AiPlugin)
+		.add_plugin(EnginePlugin)
+		.add_plugin(DrawPlugin)
+		.add_plugin(ClockPlugin)
 		.add_plugin(UIPlugin)
-		.add_startup_system(setup)
-		.run();
+		.add_startup_system(setup);
+
+	if let Some(replay) = replay {
+		app.insert_resource(replay);
+	}
+
+	if skip_menu {
+		app.insert_resource(NextState(Some(AppState::Setup)));
+	}
+
+	app.run();
+}
+
+// Value following `name` on the command line, e.g. the path in `--pgn game.pgn`
+pub fn arg_value(name: &str) -> Option<String> {
+	let args = std::env::args().collect::<Vec<String>>();
+	let i = args.iter().position(|arg| arg == name)?;
+
+	args.get(i + 1).cloned()
+}
+
+// The game starts from the position given with `--fen "<fen>"`, or the standard one
+fn start_position() -> Position {
+	match arg_value("--fen").map(|fen| Position::from_fen(&fen)) {
+		Some(Ok(position)) => position,
+		Some(Err(error)) => {
+			eprintln!("Couldn't read the starting position ({}), using the standard one", error);
+			Position::start()
+		},
+		None => Position::start()
+	}
+}
+
+// The built-in computer plays the colors given with `--ai <white|black|both>`, and
+// the engine from `--engine <path>` the ones given with `--engine-plays`
+fn players_from_args() -> Players {
+	let mut players = Players::default();
+	for (arg, player) in [("--ai", Player::Computer), ("--engine-plays", Player::Engine)] {
+		let colors = match arg_value(arg).as_deref() {
+			Some("white") => vec![PieceColor::White],
+			Some("black") => vec![PieceColor::Black],
+			Some("both") => vec![PieceColor::White, PieceColor::Black],
+			Some(color) => {
+				eprintln!("Unknown color '{}' for {}, expected white, black or both", color, arg);
+				Vec::new()
+			},
+			None => Vec::new()
+		};
+
+		for color in colors {
+			players.set(color, player);
+		}
+	}
+
+	players
+}
+
+// The engine given with `--engine <path>`, speaking UCI unless `--engine-protocol xboard` says otherwise
+fn engine_settings_from_args() -> EngineSettings {
+	let protocol = match arg_value("--engine-protocol").as_deref() {
+		Some("xboard") | Some("cecp") => EngineProtocol::XBoard,
+		Some("uci") | None => EngineProtocol::Uci,
+		Some(protocol) => {
+			eprintln!("Unknown engine protocol '{}', expected uci or xboard", protocol);
+			EngineProtocol::Uci
+		}
+	};
+
+	EngineSettings {
+		path: arg_value("--engine"),
+		protocol
+	}
+}
+
+// Seconds the computer gets per move with `--think-time <seconds>`
+fn ai_settings_from_args() -> AiSettings {
+	let mut settings = AiSettings::default();
+	if let Some(seconds) = arg_value("--think-time").and_then(|seconds| seconds.parse::<f32>().ok()) {
+		settings.think_time = std::time::Duration::from_secs_f32(seconds.max(0.));
+	}
+
+	settings
+}
+
+// Clocks for both sides with `--time-control <control>`, such as 300+2 or 40/5400+30:1800+30
+fn clock_settings_from_args() -> ClockSettings {
+	match arg_value("--time-control").map(|control| (TimeControl::parse(&control), control)) {
+		Some((Some(control), _)) => ClockSettings(Some(control)),
+		Some((None, control)) => {
+			eprintln!("Couldn't read the time control '{}', playing without clocks", control);
+			ClockSettings(None)
+		},
+		None => ClockSettings(None)
+	}
+}
+
+// Replay a PGN file given with `--pgn <path>`, picking a game other than the first with `--game <n>`
+fn replay_from_args() -> Option<Replay> {
+	let path = arg_value("--pgn")?;
+	let game = arg_value("--game").and_then(|game| game.parse().ok()).unwrap_or(1);
+
+	match load_replay(&path, game) {
+		Ok(replay) => Some(replay),
+		Err(error) => {
+			eprintln!("{}, starting a new game instead", error);
+			None
+		}
+	}
 }
 
 fn setup(mut commands: Commands) {
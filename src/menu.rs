@@ -0,0 +1,306 @@
+use bevy::{prelude::*, app::AppExit};
+use chess_core::Position;
+use crate::{board::*, engine::*, history::*, replay::*, ui::*};
+
+// Where the app is at. Games go through `Setup` to (re)start from their starting position
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Hash, States)]
+pub enum AppState {
+	#[default]
+	MainMenu,
+	Setup,
+	InGame,
+	Paused,
+	GameOver
+}
+
+// The position new games from the menu start from, the one given with `--fen` or the standard one
+#[derive(Resource)]
+pub struct StartPosition(pub Position);
+
+// Run condition for what only makes sense with a game on the board, paused or over included
+pub fn game_on_board(state: Res<State<AppState>>) -> bool {
+	state.0 != AppState::MainMenu
+}
+
+// Run condition for taking moves back and playing them again, which can pick a finished game up again
+pub fn playing_or_over(state: Res<State<AppState>>) -> bool {
+	matches!(state.0, AppState::InGame | AppState::GameOver)
+}
+
+// Despawn everything marked with `T`, when leaving the state it belongs to
+pub fn despawn_all<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+	for entity in query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+// Component to mark the root of the main menu
+#[derive(Component)]
+struct MainMenu;
+
+#[derive(Clone, Component)]
+enum MenuButton {
+	NewGame {
+		white: Player,
+		black: Player
+	},
+	// A PGN file to step through
+	LoadGame(String),
+	Quit
+}
+
+// Games saved with P or at the end of a game, newest first
+fn saved_games() -> Vec<String> {
+	let mut paths = std::fs::read_dir(".")
+		.map(|entries| entries
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.file_name().to_string_lossy().into_owned())
+			.filter(|name| name.starts_with("game-") && name.ends_with(".pgn"))
+			.collect::<Vec<String>>())
+		.unwrap_or_default();
+
+	// The names hold the time the game was saved
+	paths.sort_by(|a, b| b.cmp(a));
+	paths.truncate(5);
+
+	paths
+}
+
+fn spawn_button(parent: &mut ChildBuilder, font: &Handle<Font>, label: &str, button: impl Component) {
+	parent.spawn((
+		ButtonBundle {
+			style: Style {
+				size: Size::new(Val::Px(300.), Val::Px(45.)),
+				margin: UiRect::all(Val::Px(4.)),
+				justify_content: JustifyContent::Center,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			background_color: NORMAL_BUTTON.into(),
+			..default()
+		},
+		button
+	))
+	.with_children(|parent| {
+		parent.spawn(TextBundle::from_section(
+			label, TextStyle {
+				font: font.clone(),
+				font_size: 26.0,
+				color: Color::rgb(0.8, 0.8, 0.8)
+			}
+		));
+	});
+}
+
+// An option that's shown but can't be picked yet, greyed out
+fn spawn_disabled_button(parent: &mut ChildBuilder, font: &Handle<Font>, label: &str) {
+	parent.spawn(NodeBundle {
+		style: Style {
+			size: Size::new(Val::Px(300.), Val::Px(45.)),
+			margin: UiRect::all(Val::Px(4.)),
+			justify_content: JustifyContent::Center,
+			align_items: AlignItems::Center,
+			..default()
+		},
+		background_color: NORMAL_BUTTON.into(),
+		..default()
+	})
+	.with_children(|parent| {
+		parent.spawn(TextBundle::from_section(
+			label, TextStyle {
+				font: font.clone(),
+				font_size: 26.0,
+				color: Color::rgb(0.4, 0.4, 0.4)
+			}
+		));
+	});
+}
+
+fn spawn_heading(parent: &mut ChildBuilder, font: &Handle<Font>, text: &str, font_size: f32) {
+	parent.spawn(TextBundle::from_section(
+		text, TextStyle {
+			font: font.clone(),
+			font_size,
+			color: Color::rgb(0.8, 0.8, 0.8)
+		}
+	)
+	.with_style(Style {
+		margin: UiRect::all(Val::Px(10.)),
+		..default()
+	}));
+}
+
+fn main_menu(mut commands: Commands, asset_server: Res<AssetServer>, engine: Option<NonSend<Engine>>) {
+	let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+	let mut new_games = vec![
+		("Human vs human", Player::Human, Player::Human),
+		("Play white vs computer", Player::Human, Player::Computer),
+		("Play black vs computer", Player::Computer, Player::Human)
+	];
+	// Engine games need one to be running, from --engine
+	if engine.is_some() {
+		new_games.push(("Play white vs engine", Player::Human, Player::Engine));
+		new_games.push(("Play black vs engine", Player::Engine, Player::Human));
+		new_games.push(("Computer vs engine", Player::Computer, Player::Engine));
+	}
+
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+				flex_direction: FlexDirection::Column,
+				justify_content: JustifyContent::Center,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			..default()
+		},
+		MainMenu
+	))
+	.with_children(|parent| {
+		spawn_heading(parent, &font, "Chess!", 60.);
+
+		spawn_heading(parent, &font, "New game", 30.);
+		for (label, white, black) in new_games {
+			spawn_button(parent, &font, label, MenuButton::NewGame { white, black });
+		}
+		// There's no network player yet to play over the network with
+		spawn_disabled_button(parent, &font, "Network (coming soon)");
+
+		let saved_games = saved_games();
+		if !saved_games.is_empty() {
+			spawn_heading(parent, &font, "Load game", 30.);
+			for path in saved_games {
+				spawn_button(parent, &font, &path, MenuButton::LoadGame(path.clone()));
+			}
+		}
+
+		spawn_button(parent, &font, "Quit", MenuButton::Quit);
+	});
+}
+
+fn menu_buttons(mut commands: Commands, start_position: Res<StartPosition>, mut players: ResMut<Players>, mut history: ResMut<MoveHistory>, mut next_state: ResMut<NextState<AppState>>, mut exit: EventWriter<AppExit>, mut query: Query<(&Interaction, &MenuButton, &mut BackgroundColor, &Children), Changed<Interaction>>, mut text_query: Query<&mut Text>) {
+	for (interaction, button, mut color, children) in query.iter_mut() {
+		match *interaction {
+			Interaction::Clicked => match button {
+				MenuButton::NewGame { white, black } => {
+					players.white = *white;
+					players.black = *black;
+					history.start = start_position.0.clone();
+					commands.remove_resource::<Replay>();
+					next_state.set(AppState::Setup);
+				},
+				MenuButton::LoadGame(path) => match load_replay(path, 1) {
+					Ok(replay) => {
+						*players = Players::default();
+						history.start = replay.game().start.clone();
+						commands.insert_resource(replay);
+						next_state.set(AppState::Setup);
+					},
+					// The details go to the log, the button just says it didn't work
+					Err(error) => {
+						error!("Couldn't load the game: {}", error);
+						if let Some(mut text) = children.first().and_then(|&child| text_query.get_mut(child).ok()) {
+							text.sections[0].value = "Couldn't load this game".to_string();
+						}
+					}
+				},
+				MenuButton::Quit => exit.send(AppExit)
+			},
+			Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+			Interaction::None => *color = NORMAL_BUTTON.into()
+		}
+	}
+}
+
+// The pieces are back on their starting squares, the game can start
+fn start_game(mut next_state: ResMut<NextState<AppState>>) {
+	next_state.set(AppState::InGame);
+}
+
+// Component to mark the root of the pause menu
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Clone, Copy, Component)]
+enum PauseButton {
+	Resume,
+	MainMenu
+}
+
+// Escape pauses the game and resumes it
+fn toggle_pause(keys: Res<Input<KeyCode>>, state: Res<State<AppState>>, mut next_state: ResMut<NextState<AppState>>) {
+	if !keys.just_pressed(KeyCode::Escape) {
+		return;
+	}
+
+	match state.0 {
+		AppState::InGame => next_state.set(AppState::Paused),
+		AppState::Paused => next_state.set(AppState::InGame),
+		_ => {}
+	}
+}
+
+fn pause_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+	let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+				position_type: PositionType::Absolute,
+				justify_content: JustifyContent::Center,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			..default()
+		},
+		PauseMenu
+	))
+	.with_children(|parent| {
+		parent.spawn(NodeBundle {
+			style: Style {
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
+				padding: UiRect::all(Val::Px(20.)),
+				..default()
+			},
+			background_color: Color::rgba(0., 0., 0., 0.8).into(),
+			..default()
+		})
+		.with_children(|parent| {
+			spawn_heading(parent, &font, "Paused", 40.);
+			spawn_button(parent, &font, "Resume", PauseButton::Resume);
+			spawn_button(parent, &font, "Main menu", PauseButton::MainMenu);
+		});
+	});
+}
+
+fn pause_buttons(mut next_state: ResMut<NextState<AppState>>, mut query: Query<(&Interaction, &PauseButton, &mut BackgroundColor), Changed<Interaction>>) {
+	for (interaction, button, mut color) in query.iter_mut() {
+		match *interaction {
+			Interaction::Clicked => next_state.set(match button {
+				PauseButton::Resume => AppState::InGame,
+				PauseButton::MainMenu => AppState::MainMenu
+			}),
+			Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+			Interaction::None => *color = NORMAL_BUTTON.into()
+		}
+	}
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_system(main_menu.in_schedule(OnEnter(AppState::MainMenu)))
+			.add_system(despawn_all::<MainMenu>.in_schedule(OnExit(AppState::MainMenu)))
+			.add_system(menu_buttons.run_if(in_state(AppState::MainMenu)))
+			.add_system(start_game.in_schedule(OnEnter(AppState::Setup)))
+			.add_system(toggle_pause)
+			.add_system(pause_menu.in_schedule(OnEnter(AppState::Paused)))
+			.add_system(despawn_all::<PauseMenu>.in_schedule(OnExit(AppState::Paused)))
+			.add_system(pause_buttons.run_if(in_state(AppState::Paused)));
+	}
+}
@@ -1,16 +1,15 @@
 use bevy::prelude::*;
+use chess_core::{piece_value, Position};
+use crate::{board::*, history::*, menu::*};
 
-fn spawn_king(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh: Handle<Mesh>, mesh_cross: Handle<Mesh>, position: (u8, u8)) {
+pub use chess_core::{PieceColor, PieceType};
+
+fn spawn_king(commands: &mut Commands, material: Handle<StandardMaterial>, mesh: Handle<Mesh>, mesh_cross: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::King,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh,
@@ -27,17 +26,12 @@ fn spawn_king(commands: &mut Commands, material: Handle<StandardMaterial>, piece
     });
 }
 
-fn spawn_knight(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh_1: Handle<Mesh>, mesh_2: Handle<Mesh>, position: (u8, u8)) {
+fn spawn_knight(commands: &mut Commands, material: Handle<StandardMaterial>, mesh_1: Handle<Mesh>, mesh_2: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::Knight,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh_1,
@@ -54,17 +48,12 @@ fn spawn_knight(commands: &mut Commands, material: Handle<StandardMaterial>, pie
     });
 }
 
-fn spawn_queen(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh: Handle<Mesh>, position: (u8, u8)) {
+fn spawn_queen(commands: &mut Commands, material: Handle<StandardMaterial>, mesh: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::Queen,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh,
@@ -75,17 +64,12 @@ fn spawn_queen(commands: &mut Commands, material: Handle<StandardMaterial>, piec
     });
 }
 
-fn spawn_bishop(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh: Handle<Mesh>, position: (u8, u8)) {
+fn spawn_bishop(commands: &mut Commands, material: Handle<StandardMaterial>, mesh: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::Bishop,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh,
@@ -96,17 +80,12 @@ fn spawn_bishop(commands: &mut Commands, material: Handle<StandardMaterial>, pie
     });
 }
 
-fn spawn_rook(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh: Handle<Mesh>, position: (u8, u8)) {
+fn spawn_rook(commands: &mut Commands, material: Handle<StandardMaterial>, mesh: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::Rook,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh,
@@ -117,17 +96,12 @@ fn spawn_rook(commands: &mut Commands, material: Handle<StandardMaterial>, piece
     });
 }
 
-fn spawn_pawn(commands: &mut Commands, material: Handle<StandardMaterial>, piece_color: PieceColor, mesh: Handle<Mesh>, position: (u8, u8)) {
+fn spawn_pawn(commands: &mut Commands, material: Handle<StandardMaterial>, mesh: Handle<Mesh>, transform: Transform, piece: impl Component) {
     commands.spawn((PbrBundle {
-        transform: Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32)),
+        transform,
         ..default()
     },
-    Piece {
-        color: piece_color,
-        piece_type: PieceType::Pawn,
-        x: position.0,
-        y: position.1
-    }))
+    piece))
     .with_children(|parent| {
         parent.spawn(PbrBundle {
             mesh: mesh,
@@ -138,22 +112,6 @@ fn spawn_pawn(commands: &mut Commands, material: Handle<StandardMaterial>, piece
     });
 }
 
-#[derive(Clone, Copy, PartialEq)]
-pub enum PieceColor {
-    White,
-    Black
-}
-
-#[derive(Clone, Copy, PartialEq)]
-pub enum PieceType {
-    King,
-    Queen,
-    Bishop,
-    Knight,
-    Rook,
-    Pawn
-}
-
 #[derive(Clone, Copy, Component)]
 pub struct Piece {
     pub color: PieceColor,
@@ -163,6 +121,12 @@ pub struct Piece {
     pub y: u8
 }
 
+impl Piece {
+    pub fn square(&self) -> chess_core::Square {
+        chess_core::Square::new(self.x, self.y)
+    }
+}
+
 fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
     for (mut transform, piece) in query.iter_mut() {
         // Get the direction to move in
@@ -175,200 +139,135 @@ fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
     }
 }
 
-pub struct PiecesPlugin;
+// Meshes and materials shared by every piece, so pieces can be spawned after startup too
+#[derive(Resource)]
+pub struct PieceAssets {
+    king: Handle<Mesh>,
+    king_cross: Handle<Mesh>,
+    pawn: Handle<Mesh>,
+    knight_1: Handle<Mesh>,
+    knight_2: Handle<Mesh>,
+    rook: Handle<Mesh>,
+    bishop: Handle<Mesh>,
+    queen: Handle<Mesh>,
+    white_material: Handle<StandardMaterial>,
+    black_material: Handle<StandardMaterial>
+}
 
-impl Plugin for PiecesPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_startup_system(create_pieces)
-            .add_system(move_pieces);
+impl FromWorld for PieceAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut materials = world.get_resource_mut::<Assets<StandardMaterial>>().unwrap();
+
+        PieceAssets {
+            king: asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0"),
+            king_cross: asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0"),
+            pawn: asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0"),
+            knight_1: asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0"),
+            knight_2: asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0"),
+            rook: asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0"),
+            bishop: asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0"),
+            queen: asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0"),
+            white_material: materials.add(Color::rgb(1., 0.8, 0.8).into()),
+            black_material: materials.add(Color::rgb(0., 0.2, 0.2).into())
+        }
     }
 }
 
-fn create_pieces(mut commands: Commands, asset_server: Res<AssetServer>, mut materials: ResMut<Assets<StandardMaterial>>) {
-	// Load all the meshes
-	let king_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0");
-	let king_cross_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0");
-	let pawn_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0");
-	let knight_1_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0");
-	let knight_2_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0");
-	let rook_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0");
-	let bishop_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0");
-	let queen_handle: Handle<Mesh> = asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0");
-
-	// Add some materials
-	let white_material = materials.add(Color::rgb(1., 0.8, 0.8).into());
-	let black_material = materials.add(Color::rgb(0., 0.2, 0.2).into());
-
-	spawn_rook(&mut commands, white_material.clone(), PieceColor::White, rook_handle.clone(), (0, 0));
-	spawn_knight(&mut commands, white_material.clone(), PieceColor::White, knight_1_handle.clone(), knight_2_handle.clone(), (0, 1));
-	spawn_bishop(&mut commands, white_material.clone(), PieceColor::White, bishop_handle.clone(), (0, 2));
-	spawn_queen(&mut commands, white_material.clone(), PieceColor::White, queen_handle.clone(), (0, 3));
-	spawn_king(&mut commands, white_material.clone(), PieceColor::White, king_handle.clone(), king_cross_handle.clone(), (0, 4));
-	spawn_bishop(&mut commands, white_material.clone(), PieceColor::White, bishop_handle.clone(), (0, 5));
-	spawn_knight(&mut commands, white_material.clone(), PieceColor::White, knight_1_handle.clone(), knight_2_handle.clone(), (0, 6));
-	spawn_rook(&mut commands, white_material.clone(), PieceColor::White, rook_handle.clone(), (0, 7));
-
-	for i in 0..8 {
-		spawn_pawn(&mut commands, white_material.clone(), PieceColor::White, pawn_handle.clone(), (1, i));
-	}
-
-	spawn_rook(&mut commands, black_material.clone(), PieceColor::Black, rook_handle.clone(), (7, 0));
-	spawn_knight(&mut commands, black_material.clone(), PieceColor::Black, knight_1_handle.clone(), knight_2_handle.clone(), (7, 1));
-	spawn_bishop(&mut commands, black_material.clone(), PieceColor::Black, bishop_handle.clone(), (7, 2));
-	spawn_queen(&mut commands, black_material.clone(), PieceColor::Black, queen_handle.clone(), (7, 3));
-	spawn_king(&mut commands, black_material.clone(), PieceColor::Black, king_handle.clone(), king_cross_handle.clone(), (7, 4));
-	spawn_bishop(&mut commands, black_material.clone(), PieceColor::Black, bishop_handle.clone(), (7, 5));
-	spawn_knight(&mut commands, black_material.clone(), PieceColor::Black, knight_1_handle.clone(), knight_2_handle.clone(), (7, 6));
-	spawn_rook(&mut commands, black_material.clone(), PieceColor::Black, rook_handle.clone(), (7, 7));
+pub fn spawn_piece(commands: &mut Commands, piece_assets: &PieceAssets, piece_color: PieceColor, piece_type: PieceType, position: (u8, u8)) {
+    let transform = Transform::from_translation(Vec3::new(position.0 as f32, 0., position.1 as f32));
+    let piece = Piece {
+        color: piece_color,
+        piece_type,
+        x: position.0,
+        y: position.1
+    };
 
-	for i in 0..8 {
-		spawn_pawn(&mut commands, black_material.clone(), PieceColor::Black, pawn_handle.clone(), (6, i));
-	}
+    spawn_model(commands, piece_assets, piece_color, piece_type, transform, piece);
 }
 
-fn color_of_square(pos: (u8, u8), pieces: &Vec<Piece>) -> Option<PieceColor> {
-	for piece in pieces {
-		if piece.x == pos.0 && piece.y == pos.1 {
-			return Some(piece.color);
-		}
-	}
-
-	None
+// The model of a piece, marked with `piece` for what it's used for
+fn spawn_model(commands: &mut Commands, piece_assets: &PieceAssets, piece_color: PieceColor, piece_type: PieceType, transform: Transform, piece: impl Component) {
+    let material = match piece_color {
+        PieceColor::White => piece_assets.white_material.clone(),
+        PieceColor::Black => piece_assets.black_material.clone()
+    };
+
+    match piece_type {
+        PieceType::King => spawn_king(commands, material, piece_assets.king.clone(), piece_assets.king_cross.clone(), transform, piece),
+        PieceType::Queen => spawn_queen(commands, material, piece_assets.queen.clone(), transform, piece),
+        PieceType::Bishop => spawn_bishop(commands, material, piece_assets.bishop.clone(), transform, piece),
+        PieceType::Knight => spawn_knight(commands, material, piece_assets.knight_1.clone(), piece_assets.knight_2.clone(), transform, piece),
+        PieceType::Rook => spawn_rook(commands, material, piece_assets.rook.clone(), transform, piece),
+        PieceType::Pawn => spawn_pawn(commands, material, piece_assets.pawn.clone(), transform, piece)
+    }
 }
 
-fn is_path_empty(begin: (u8, u8), end: (u8, u8), pieces: &Vec<Piece>) -> bool {
-	// Same column
-	if begin.0 == end.0 {
-		for piece in pieces {
-			if piece.x == begin.0 && ((piece.y > begin.1 && piece.y < end.1) || (piece.y > end.1 && piece.y < begin.1)) {
-				return false;
-			}
-		}
-	}
-
-	// Same row
-	if begin.1 == end.1 {
-		for piece in pieces {
-			if piece.y == begin.1 && ((piece.x > begin.0 && piece.x < end.0) || (piece.x > end.0 && piece.x < begin.0)) {
-				return false;
-			}
-		}
-	}
-
-	// Diagonals
-	let x_diff = (begin.0 as i8 - end.0 as i8).abs();
-	let y_diff = (begin.1 as i8 - end.1 as i8).abs();
-
-	if x_diff == y_diff {
-		for i in 1..x_diff {
-			let pos = if begin.0 < end.0 && begin.1 < end.1 {
-				// left bottom - right top
-				(begin.0 + i as u8, begin.1 + i as u8)
-			} else if begin.0 < end.0 && begin.1 > end.1 {
-				// left top - right bottom
-				(begin.0 + i as u8, begin.1 - i as u8)
-			} else if begin.0 > end.0 && begin.1 < end.1 {
-				// right bottom - left top
-				(begin.0 - i as u8, begin.1 + i as u8)
-			} else {
-				// begin.0 > end.0 && begin.1 > end.1
-				// right top - left bottom
-				(begin.0 - i as u8, begin.1 - i as u8)
-			};
-
-			if color_of_square(pos, pieces).is_some() {
-				return false;
-			}
-		}
-	}
+// Replace the piece entities with the pieces of `position`, after going more than a move
+// back or forth in the game
+pub fn respawn_pieces(commands: &mut Commands, piece_assets: &PieceAssets, position: &Position, pieces_query: &Query<(Entity, &mut Piece), Without<Taken>>) {
+    for (entity, _piece) in pieces_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 
-	true
+    for (square, piece) in position.pieces() {
+        spawn_piece(commands, piece_assets, piece.color, piece.piece_type, (square.rank(), square.file()));
+    }
 }
 
-impl Piece {
-    pub fn is_move_valid(&self, new_position: (u8, u8), pieces: Vec<Piece>) -> bool {
-        // If there's a piece of the same color in the same square, it can't move
-        if color_of_square(new_position, &pieces) == Some(self.color) {
-            return false;
-        }
+// Component to mark a piece taken off the board, lined up beside it on the side of who took it
+#[derive(Component)]
+struct CapturedPiece;
 
-        match self.piece_type {
-            PieceType::King => {
-                // Horizontal
-                ((self.x as i8 - new_position.0 as i8).abs() == 1 && (self.y == new_position.1))
-                // Vertical
-                || ((self.y as i8 - new_position.1 as i8).abs() == 1 && (self.x == new_position.0))
-                // Diagnoal
-                || ((self.x as i8 - new_position.0 as i8).abs() == 1 && (self.y as i8 - new_position.1 as i8).abs() == 1)
-            },
-            PieceType::Queen => {
-                is_path_empty((self.x, self.y), new_position, &pieces)
-                && ((self.x as i8 - new_position.0 as i8).abs() == (self.y as i8 - new_position.1 as i8).abs()
-                    || ((self.x == new_position.0 && self.y != new_position.1)
-                    || (self.y == new_position.1 && self.x != new_position.0)))
-            },
-            PieceType::Bishop => {
-                is_path_empty((self.x, self.y), new_position, &pieces)
-                && (self.x as i8 - new_position.0 as i8).abs() == (self.y as i8 - new_position.1 as i8).abs()
-            },
-            PieceType::Knight => {
-                ((self.x as i8 - new_position.0 as i8).abs() == 2
-                    && (self.y as i8 - new_position.1 as i8).abs() == 1)
-                    || ((self.x as i8 - new_position.0 as i8).abs() == 1
-                        && (self.y as i8 - new_position.1 as i8).abs() == 2)
-            },
-            PieceType::Rook => {
-                is_path_empty((self.x, self.y), new_position, &pieces)
-                    && ((self.x == new_position.0 && self.y != new_position.1)
-                        || (self.y == new_position.1 && self.x != new_position.0))
-            },
-            PieceType::Pawn => {
-                if self.color == PieceColor::White {
-                    // Normal move
-                    if new_position.0 as i8 - self.x as i8 == 1 && (self.y == new_position.1) {
-                        if color_of_square(new_position, &pieces).is_none() {
-                            return true;
-                        }
-                    }
+// Line the pieces captured up to the ply on the board beside it, the most valuable first
+fn show_captured_pieces(mut commands: Commands, piece_assets: Res<PieceAssets>, history: Res<MoveHistory>, browsing: Res<Browsing>, query: Query<Entity, With<CapturedPiece>>) {
+    if !history.is_changed() && !browsing.is_changed() {
+        return;
+    }
 
-                    // Move 2 sqauares
-                    if self.x == 1 && new_position.0 as i8 - self.x as i8 == 2 && (self.y == new_position.1) && is_path_empty((self.x, self.y), new_position, &pieces) {
-                        if color_of_square(new_position, &pieces).is_none() {
-                            return true;
-                        }
-                    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 
-                    // Take piece
-                    if new_position.0 as i8 - self.x as i8 == 1 && (self.y as i8 - new_position.1 as i8).abs() == 1 {
-                        if color_of_square(new_position, &pieces) == Some(PieceColor::Black) {
-                            return true;
-                        }
-                    }
-                } else {
-                    // Normal move
-                    if new_position.0 as i8 - self.x as i8 == -1 && (self.y == new_position.1) {
-                        if color_of_square(new_position, &pieces).is_none() {
-                            return true;
-                        }
-                    }
+    let mut captured = history.undos[..browsing.ply(&history)].iter()
+        .filter_map(|undo| undo.captured())
+        .map(|(_square, piece)| piece)
+        .collect::<Vec<chess_core::Piece>>();
+    captured.sort_by_key(|piece| -piece_value(piece.piece_type));
+
+    for color in [PieceColor::White, PieceColor::Black] {
+        // White's captures go along the a-file side starting from its end of the board, black's
+        // along the h-file side from the other end
+        let pieces = captured.iter().filter(|piece| piece.color == color.opposite());
+        for (i, piece) in pieces.enumerate() {
+            let translation = match color {
+                PieceColor::White => Vec3::new(i as f32 * 0.5, 0., -1.),
+                PieceColor::Black => Vec3::new(7. - i as f32 * 0.5, 0., 8.)
+            };
+            let transform = Transform::from_translation(translation).with_scale(Vec3::splat(0.5));
+
+            spawn_model(&mut commands, &piece_assets, piece.color, piece.piece_type, transform, CapturedPiece);
+        }
+    }
+}
 
-                    // Move 2 sqauares
-                    if self.x == 6 && new_position.0 as i8 - self.x as i8 == -2 && (self.y == new_position.1) && is_path_empty((self.x, self.y), new_position, &pieces) {
-                        if color_of_square(new_position, &pieces).is_none() {
-                            return true;
-                        }
-                    }
+pub struct PiecesPlugin;
 
-                    // Take piece
-                    if new_position.0 as i8 - self.x as i8 == -1 && (self.y as i8 - new_position.1 as i8).abs() == 1 {
-                        if color_of_square(new_position, &pieces) == Some(PieceColor::White) {
-                            return true;
-                        }
-                    }
-                }
+impl Plugin for PiecesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PieceAssets>()
+            .add_system(despawn_all::<Piece>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<Piece>.in_schedule(OnEnter(AppState::Setup)))
+            .add_system(create_pieces.after(reset_game).in_schedule(OnEnter(AppState::Setup)))
+            .add_system(despawn_all::<CapturedPiece>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(show_captured_pieces.run_if(game_on_board))
+            .add_system(move_pieces);
+    }
+}
 
-                false
-            }
-        }
+fn create_pieces(mut commands: Commands, piece_assets: Res<PieceAssets>, position: Res<GamePosition>) {
+    for (square, piece) in position.0.pieces() {
+        spawn_piece(&mut commands, &piece_assets, piece.color, piece.piece_type, (square.rank(), square.file()));
     }
-}
\ No newline at end of file
+}
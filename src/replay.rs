@@ -0,0 +1,72 @@
+use bevy::prelude::*;
+use chess_core::{parse_pgn, PgnGame};
+use crate::{board::*, clock::*, history::*, menu::*, pieces::*};
+
+// A game loaded from PGN being stepped through, instead of played. The moves stepped
+// through so far are the ones in `MoveHistory`
+#[derive(Resource)]
+pub struct Replay {
+	game: PgnGame
+}
+
+impl Replay {
+	pub fn new(game: PgnGame) -> Self {
+		Replay {
+			game
+		}
+	}
+
+	pub fn game(&self) -> &PgnGame {
+		&self.game
+	}
+}
+
+// Load game number `game` (starting from 1) of a PGN file
+pub fn load_replay(path: &str, game: usize) -> Result<Replay, String> {
+	let text = std::fs::read_to_string(path).map_err(|error| format!("couldn't read {}: {}", path, error))?;
+	let mut games = parse_pgn(&text).map_err(|error| format!("couldn't read {}: {}", path, error))?;
+
+	if game == 0 || game > games.len() {
+		return Err(format!("{} has {} games, there's no game {}", path, games.len(), game));
+	}
+
+	Ok(Replay::new(games.swap_remove(game - 1)))
+}
+
+// Run conditions for the systems that let the players move pieces, and for the replay ones
+pub fn not_replaying(replay: Option<Res<Replay>>) -> bool {
+	replay.is_none()
+}
+
+fn replaying(replay: Option<Res<Replay>>) -> bool {
+	replay.is_some()
+}
+
+// Step through the game with the left and right arrow keys
+fn step_replay(mut commands: Commands, keys: Res<Input<KeyCode>>, replay: Res<Replay>, mut position: ResMut<GamePosition>, mut history: ResMut<MoveHistory>, clock: Res<GameClock>, mut turn: ResMut<PlayerTurn>, piece_assets: Res<PieceAssets>, mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>) {
+	if keys.just_pressed(KeyCode::Right) {
+		let mv = if let Some(mv) = replay.game.moves.get(history.moves.len()) {
+			*mv
+		} else {
+			info!("End of the game: {}", replay.game.result);
+			return;
+		};
+
+		play_move_entities(&mut commands, &piece_assets, &position.0, mv, &mut pieces_query);
+		history.play(&mut position.0, mv, &clock);
+		turn.change();
+	} else if keys.just_pressed(KeyCode::Left) {
+		if let Some((mv, undo)) = history.take_back(&mut position.0) {
+			unmove_piece_entities(&mut commands, &piece_assets, &position.0, mv, undo.captured(), &mut pieces_query);
+			turn.change();
+		}
+	}
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_system(step_replay.run_if(replaying).run_if(not_browsing).run_if(in_state(AppState::InGame)));
+	}
+}
@@ -1,14 +1,30 @@
-use crate::{board::*, pieces::*};
-use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig};
+use crate::{board::*, clock::*, draw::*, engine::*, history::*, menu::*, pieces::*};
+use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig, input::mouse::{MouseScrollUnit, MouseWheel}};
+use chess_core::{piece_value, PgnResult, Position};
+use std::time::Duration;
 
 // Component to mark the Text entity
 #[derive(Component)]
 struct NextMoveText;
 
-// Initialize UiCamera and text
-fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>) {
-    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+// Component to mark the line showing the engine's analysis
+#[derive(Component)]
+struct AnalysisText;
+
+// Component to mark the line about draw offers and claims
+#[derive(Component)]
+struct DrawText;
+
+// Component to mark the line showing who is ahead in material
+#[derive(Component)]
+struct MaterialText;
 
+// Component to mark the clocks, one section for each side
+#[derive(Component)]
+struct ClockText;
+
+// Initialize UiCamera, for the menus as well as the game
+fn init_ui_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle {
         camera: Camera {
             order: 2,
@@ -20,10 +36,16 @@ fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>
         },
         ..default()
     });
+}
+
+// Text shown while a game is on the board
+fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
     commands.spawn((
         TextBundle::from_section(
             "Next Move: White", TextStyle {
-                font: font,
+                font: font.clone(),
                 font_size: 40.0,
                 color: Color::rgb(0.8, 0.8, 0.8)
             }
@@ -40,26 +62,663 @@ fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>
         }),
         NextMoveText
     ));
+    commands.spawn((
+        TextBundle::from_section(
+            "", TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::rgb(0.8, 0.8, 0.8)
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(10.),
+                top: Val::Px(55.),
+                ..default()
+            },
+            ..default()
+        }),
+        AnalysisText
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "", TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::rgb(0.9, 0.7, 0.3)
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(10.),
+                top: Val::Px(85.),
+                ..default()
+            },
+            ..default()
+        }),
+        DrawText
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "", TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::rgb(0.8, 0.8, 0.8)
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(10.),
+                top: Val::Px(115.),
+                ..default()
+            },
+            ..default()
+        }),
+        MaterialText
+    ));
+    let clock_style = TextStyle {
+        font,
+        font_size: 30.0,
+        color: Color::rgb(0.8, 0.8, 0.8)
+    };
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("", clock_style.clone()),
+            TextSection::new("", clock_style)
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(10.),
+                top: Val::Px(10.),
+                ..default()
+            },
+            ..default()
+        }),
+        ClockText
+    ));
 }
 
-fn next_move_text_update(mut _commands: Commands, turn: Res<PlayerTurn>, mut query: Query<(&mut Text, &NextMoveText)>) {
-    if !turn.is_changed() {
+fn next_move_text_update(mut _commands: Commands, turn: Res<PlayerTurn>, history: Res<MoveHistory>, outcome: Res<GameOutcome>, mut query: Query<(&mut Text, &NextMoveText)>) {
+    if !turn.is_changed() && !outcome.is_changed() {
         return;
     }
 
+    let next_move = match outcome.0 {
+        Some(result) => format!("{}!", result),
+        None => format!("Next move: {}", color_name(turn.0))
+    };
+
     for (mut text,  _tag) in query.iter_mut() {
-        text.sections[0].value = format!("Next move: {}", match turn.0 {
-            PieceColor::White => "White",
-            PieceColor::Black => "Black"
+        text.sections[0].value = match history.sans.last() {
+            Some(last_move) => format!("{} (last: {})", next_move, last_move),
+            None => next_move.clone()
+        };
+    }
+}
+
+// Let the players know about draw offers, draws they can claim and resigning
+fn draw_text_update(turn: Res<PlayerTurn>, players: Res<Players>, position: Res<GamePosition>, history: Res<MoveHistory>, offer: Res<DrawOffer>, outcome: Res<GameOutcome>, resign_prompt: Res<ResignPrompt>, mut query: Query<&mut Text, With<DrawText>>) {
+    if !turn.is_changed() && !offer.is_changed() && !outcome.is_changed() && !resign_prompt.is_changed() {
+        return;
+    }
+
+    let human_to_move = players.get(turn.0) == Player::Human;
+    let draw_text = if outcome.0.is_some() {
+        String::new()
+    } else if resign_prompt.0.is_some() {
+        "Press R again to resign".to_string()
+    } else if offer.0 == Some(turn.0.opposite()) && human_to_move {
+        format!("{} offers a draw, press O to accept", color_name(turn.0.opposite()))
+    } else if let Some(color) = offer.0 {
+        format!("{} offered a draw", color_name(color))
+    } else if let Some(reason) = position.0.claimable_draw(&history.hashes).filter(|_| human_to_move) {
+        format!("Press D to claim a draw by {}", reason)
+    } else {
+        String::new()
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = draw_text.clone();
+    }
+}
+
+// Pieces counted with the same values the captured pieces are sorted by, in whole pawns
+fn material(position: &Position, color: PieceColor) -> i32 {
+    position.pieces()
+        .filter(|(_square, piece)| piece.color == color)
+        .map(|(_square, piece)| piece_value(piece.piece_type) / 100)
+        .sum()
+}
+
+// The material difference on the board, such as "White +3", and nothing when it's even
+fn material_text_update(position: Res<GamePosition>, history: Res<MoveHistory>, browsing: Res<Browsing>, mut query: Query<&mut Text, With<MaterialText>>) {
+    if !history.is_changed() && !browsing.is_changed() {
+        return;
+    }
+
+    let shown = browsing.position(&position);
+    let difference = material(shown, PieceColor::White) - material(shown, PieceColor::Black);
+    let material_text = match difference {
+        0 => String::new(),
+        difference if difference > 0 => format!("{} +{}", color_name(PieceColor::White), difference),
+        difference => format!("{} +{}", color_name(PieceColor::Black), -difference)
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = material_text.clone();
+    }
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black"
+    }
+}
+
+// Minutes and seconds, with tenths once it gets close
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else if seconds >= 10 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    }
+}
+
+// The clocks tick every frame, the side to move's one highlighted
+fn clock_text_update(game_clock: Res<GameClock>, turn: Res<PlayerTurn>, outcome: Res<GameOutcome>, mut query: Query<&mut Text, With<ClockText>>) {
+    for mut text in query.iter_mut() {
+        for (section, color) in text.sections.iter_mut().zip([PieceColor::White, PieceColor::Black]) {
+            section.value = match game_clock.time_left(color, &turn) {
+                Some(time) if color == PieceColor::White => format!("{} {}  ", color_name(color), format_clock(time)),
+                Some(time) => format!("{} {}", color_name(color), format_clock(time)),
+                None => String::new()
+            };
+            section.style.color = if color == turn.0 && outcome.0.is_none() {
+                Color::rgb(0.9, 0.7, 0.3)
+            } else {
+                Color::rgb(0.8, 0.8, 0.8)
+            };
+        }
+    }
+}
+
+fn analysis_text_update(analysis: Res<EngineAnalysis>, mut query: Query<&mut Text, With<AnalysisText>>) {
+    if !analysis.is_changed() {
+        return;
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = analysis.0.clone().unwrap_or_default();
+    }
+}
+
+// Component to mark the panel the move list scrolls in
+#[derive(Component)]
+struct MoveListPanel;
+
+// Component to mark the move list, scrolled by moving it up inside its panel
+#[derive(Component)]
+struct MoveList {
+    position: f32,
+    // Keeps the latest move in view, until the list is scrolled up
+    follow: bool
+}
+
+// A move in the list, clicking it shows the board after it
+#[derive(Component)]
+struct PlyButton(usize);
+
+const CURRENT_PLY: Color = Color::rgb(0.35, 0.3, 0.15);
+
+fn init_move_list(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(50.),
+                    bottom: Val::Px(10.),
+                    ..default()
+                },
+                size: Size::width(Val::Px(200.)),
+                flex_direction: FlexDirection::Column,
+                overflow: Overflow::Hidden,
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            ..default()
+        },
+        MoveListPanel
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    flex_shrink: 0.,
+                    padding: UiRect::all(Val::Px(5.)),
+                    ..default()
+                },
+                ..default()
+            },
+            MoveList {
+                position: 0.,
+                follow: true
+            }
+        ));
+    });
+}
+
+fn ply_color(ply: usize, current: usize) -> Color {
+    if ply == current {
+        CURRENT_PLY
+    } else {
+        Color::NONE
+    }
+}
+
+// Rebuild the list whenever a move is played or taken back, the board shows another ply or the game ends
+fn move_list_update(mut commands: Commands, asset_server: Res<AssetServer>, history: Res<MoveHistory>, browsing: Res<Browsing>, outcome: Res<GameOutcome>, mut query: Query<(Entity, &mut MoveList)>) {
+    if !history.is_changed() && !browsing.is_changed() && !outcome.is_changed() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = TextStyle {
+        font,
+        font_size: 22.0,
+        color: Color::rgb(0.8, 0.8, 0.8)
+    };
+    let current = browsing.ply(&history);
+
+    // Move number and the plies of white's and black's moves, a game set up with black to move
+    // starting halfway through its first row
+    let mut rows: Vec<(u32, [Option<usize>; 2])> = Vec::new();
+    let mut number = history.start.fullmove_number();
+    let mut color = history.start.side_to_move();
+    for ply in 1..=history.sans.len() {
+        if color == PieceColor::White || rows.is_empty() {
+            rows.push((number, [None, None]));
+        }
+        if let Some((_, plies)) = rows.last_mut() {
+            plies[color.index()] = Some(ply);
+        }
+        if color == PieceColor::Black {
+            number += 1;
+        }
+        color = color.opposite();
+    }
+
+    for (entity, mut move_list) in query.iter_mut() {
+        // New moves scroll into view
+        if history.is_changed() {
+            move_list.follow = true;
+        }
+
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            for (number, plies) in &rows {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        flex_shrink: 0.,
+                        size: Size::height(Val::Px(28.)),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(format!("{}.", number), text_style.clone())
+                        .with_style(Style {
+                            size: Size::width(Val::Px(40.)),
+                            ..default()
+                        }));
+
+                    for (i, ply) in plies.iter().enumerate() {
+                        let cell = Style {
+                            size: Size::new(Val::Px(70.), Val::Px(26.)),
+                            padding: UiRect::left(Val::Px(4.)),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        };
+                        match ply {
+                            Some(ply) => {
+                                parent.spawn((
+                                    ButtonBundle {
+                                        style: cell,
+                                        background_color: ply_color(*ply, current).into(),
+                                        ..default()
+                                    },
+                                    PlyButton(*ply)
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(history.sans[ply - 1].clone(), text_style.clone()));
+                                });
+                            },
+                            // Black moved first
+                            None if i == 0 => {
+                                parent.spawn(NodeBundle {
+                                    style: cell,
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section("...", text_style.clone()));
+                                });
+                            },
+                            None => {}
+                        }
+                    }
+                });
+            }
+
+            if let Some(result) = outcome.0 {
+                parent.spawn(TextBundle::from_section(PgnResult::from(result).to_string(), TextStyle {
+                    color: Color::rgb(0.9, 0.7, 0.3),
+                    ..text_style.clone()
+                })
+                .with_style(Style {
+                    flex_shrink: 0.,
+                    margin: UiRect::all(Val::Px(4.)),
+                    ..default()
+                }));
+            }
         });
     }
 }
 
+// Scroll with the mouse wheel, and follow the game down as moves come in
+fn scroll_move_list(mut wheel_events: EventReader<MouseWheel>, mut query: Query<(&mut MoveList, &mut Style, &Parent, &Node)>, panels_query: Query<&Node, With<MoveListPanel>>) {
+    let scrolled = wheel_events.iter().map(|event| match event.unit {
+        MouseScrollUnit::Line => event.y * 28.,
+        MouseScrollUnit::Pixel => event.y
+    }).sum::<f32>();
+
+    for (mut move_list, mut style, parent, node) in query.iter_mut() {
+        let panel_height = panels_query.get(parent.get()).map_or(0., |panel| panel.size().y);
+        let max_scroll = (node.size().y - panel_height).max(0.);
+
+        if scrolled != 0. {
+            let position = (move_list.position + scrolled).clamp(-max_scroll, 0.);
+            move_list.position = position;
+            move_list.follow = position <= -max_scroll;
+        } else if move_list.follow {
+            move_list.position = -max_scroll;
+        }
+
+        if style.position.top != Val::Px(move_list.position) {
+            style.position.top = Val::Px(move_list.position);
+        }
+    }
+}
+
+fn ply_buttons(history: Res<MoveHistory>, browsing: Res<Browsing>, mut browse_event: EventWriter<BrowseEvent>, mut query: Query<(&Interaction, &PlyButton, &mut BackgroundColor), Changed<Interaction>>) {
+    let current = browsing.ply(&history);
+
+    for (interaction, button, mut color) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => browse_event.send(BrowseEvent(button.0)),
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = ply_color(button.0, current).into()
+        }
+    }
+}
+
+pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+
+// Component to mark the root of the promotion overlay
+#[derive(Component)]
+struct PromotionDialog;
+
+#[derive(Component)]
+struct PromotionButton(PieceType);
+
+// Show the piece choice while a pawn is waiting to be promoted
+fn promotion_dialog(mut commands: Commands, asset_server: Res<AssetServer>, pending_promotion: Res<PendingPromotion>, query: Query<Entity, With<PromotionDialog>>) {
+    if !pending_promotion.is_changed() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if pending_promotion.0.is_none() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        },
+        PromotionDialog
+    ))
+    .with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(10.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.8).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Promote to", TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: Color::rgb(0.8, 0.8, 0.8)
+                }
+            ));
+
+            for piece_type in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(150.), Val::Px(50.)),
+                            margin: UiRect::all(Val::Px(5.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    PromotionButton(piece_type)
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        match piece_type {
+                            PieceType::Queen => "Queen",
+                            PieceType::Rook => "Rook",
+                            PieceType::Bishop => "Bishop",
+                            _ => "Knight"
+                        }, TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::rgb(0.8, 0.8, 0.8)
+                        }
+                    ));
+                });
+            }
+        });
+    });
+}
+
+fn promotion_buttons(mut promotion_event: EventWriter<PromotionEvent>, mut query: Query<(&Interaction, &PromotionButton, &mut BackgroundColor), Changed<Interaction>>) {
+    for (interaction, button, mut color) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => promotion_event.send(PromotionEvent(button.0)),
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into()
+        }
+    }
+}
+
+// Component to mark the root of the game over overlay
+#[derive(Component)]
+struct GameOverDialog;
+
+#[derive(Clone, Copy, Component)]
+enum GameOverButton {
+    Rematch,
+    SwapColors,
+    ExportPgn,
+    MainMenu
+}
+
+// Show how the game ended, with what to do next
+fn game_over_dialog(mut commands: Commands, asset_server: Res<AssetServer>, outcome: Res<GameOutcome>) {
+    let result = if let Some(result) = outcome.0 {
+        result
+    } else {
+        return;
+    };
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        },
+        GameOverDialog
+    ))
+    .with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.8).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Game over", TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: Color::rgb(0.8, 0.8, 0.8)
+                }
+            ));
+            parent.spawn(TextBundle::from_section(
+                result.to_string(), TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: Color::rgb(0.9, 0.7, 0.3)
+                }
+            )
+            .with_style(Style {
+                margin: UiRect::all(Val::Px(10.)),
+                ..default()
+            }));
+
+            for button in [GameOverButton::Rematch, GameOverButton::SwapColors, GameOverButton::ExportPgn, GameOverButton::MainMenu] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(250.), Val::Px(50.)),
+                            margin: UiRect::all(Val::Px(5.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    button
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        match button {
+                            GameOverButton::Rematch => "Rematch",
+                            GameOverButton::SwapColors => "Swap colors",
+                            GameOverButton::ExportPgn => "Export PGN",
+                            GameOverButton::MainMenu => "Main menu"
+                        }, TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::rgb(0.8, 0.8, 0.8)
+                        }
+                    ));
+                });
+            }
+        });
+    });
+}
+
+fn game_over_buttons(mut players: ResMut<Players>, mut next_state: ResMut<NextState<AppState>>, mut export_event: EventWriter<ExportPgnEvent>, mut query: Query<(&Interaction, &GameOverButton, &mut BackgroundColor), Changed<Interaction>>) {
+    for (interaction, button, mut color) in query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => match button {
+                GameOverButton::Rematch => next_state.set(AppState::Setup),
+                GameOverButton::SwapColors => {
+                    players.swap();
+                    next_state.set(AppState::Setup);
+                },
+                GameOverButton::ExportPgn => export_event.send(ExportPgnEvent),
+                GameOverButton::MainMenu => next_state.set(AppState::MainMenu)
+            },
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into()
+        }
+    }
+}
+
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(init_next_move_text)
-            .add_system(next_move_text_update);
+        app.add_startup_system(init_ui_camera)
+            .add_system(init_next_move_text.in_schedule(OnExit(AppState::MainMenu)))
+            .add_system(despawn_all::<NextMoveText>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<AnalysisText>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<DrawText>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<MaterialText>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<ClockText>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(init_move_list.in_schedule(OnExit(AppState::MainMenu)))
+            .add_system(despawn_all::<MoveListPanel>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(despawn_all::<PromotionDialog>.in_schedule(OnEnter(AppState::MainMenu)))
+            .add_system(next_move_text_update)
+            .add_system(analysis_text_update)
+            .add_system(draw_text_update)
+            .add_system(material_text_update)
+            .add_system(clock_text_update)
+            .add_system(move_list_update)
+            .add_system(scroll_move_list.after(move_list_update))
+            .add_system(ply_buttons.run_if(playing_or_over))
+            .add_system(promotion_dialog)
+            .add_system(promotion_buttons)
+            .add_system(game_over_dialog.in_schedule(OnEnter(AppState::GameOver)))
+            .add_system(despawn_all::<GameOverDialog>.in_schedule(OnExit(AppState::GameOver)))
+            .add_system(game_over_buttons.run_if(in_state(AppState::GameOver)));
     }
 }
\ No newline at end of file